@@ -0,0 +1,488 @@
+use crate::header::{Opcode, RCode};
+use crate::name::{is_subdomain, normalize, parent};
+use crate::packet::Packet;
+use crate::question::{QClass, QType, Question};
+use crate::record::Record;
+use crate::server::{response_to, RequestHandler};
+use std::collections::{BTreeMap, HashSet};
+
+/// How many CNAMEs to follow within our own zones before giving up, which
+/// protects against loops.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The records of a single zone, kept in memory and indexed by owner name.
+#[derive(Debug, Clone, Default)]
+pub struct Zone {
+    pub origin: String,
+    records: BTreeMap<String, Vec<Record>>,
+    /// Every owner name, and every name between one and the origin, so
+    /// that telling whether a name exists takes a single lookup
+    names: HashSet<String>,
+}
+
+impl Zone {
+    pub fn new(origin: &str) -> Zone {
+        Zone {
+            origin: normalize(origin),
+            records: BTreeMap::new(),
+            names: HashSet::new(),
+        }
+    }
+
+    /// Add a record to the zone. Records with an owner name outside the zone
+    /// are ignored and returned as an error.
+    pub fn add(&mut self, record: Record) -> Result<(), Record> {
+        let name = normalize(record.domain());
+        if !is_subdomain(&name, &self.origin) {
+            return Err(record);
+        }
+
+        let mut current = Some(name.as_str());
+        while let Some(ancestor) = current {
+            // Its ancestors went in along with it.
+            if !self.names.insert(ancestor.to_string()) || ancestor == self.origin {
+                break;
+            }
+            current = parent(ancestor);
+        }
+
+        let records = self.records.entry(name).or_default();
+        if !records.contains(&record) {
+            records.push(record);
+        }
+        Ok(())
+    }
+
+    /// The SOA record at the zone apex
+    pub fn soa(&self) -> Option<&Record> {
+        self.lookup(&self.origin)
+            .iter()
+            .find(|record| record.qtype() == QType::SOA)
+    }
+
+    /// All records owned by exactly `name`
+    pub fn lookup(&self, name: &str) -> &[Record] {
+        self.records
+            .get(&normalize(name))
+            .map_or(&[], |records| records.as_slice())
+    }
+
+    /// Every record in the zone, ordered by owner name
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.records.values().flatten()
+    }
+
+    /// Whether `name` owns records or has descendants that do. A name
+    /// without records that has descendants is an empty non-terminal, which
+    /// exists even though it owns nothing (RFC 8020).
+    pub fn name_exists(&self, name: &str) -> bool {
+        self.names.contains(&normalize(name))
+    }
+
+    /// The NS records of the closest delegation point between the apex
+    /// (exclusive) and `name` (inclusive), if `name` has been delegated away
+    /// from this zone.
+    pub fn delegation(&self, name: &str) -> Option<Vec<Record>> {
+        let name = normalize(name);
+        let mut cut = None;
+        let mut current = Some(name.as_str());
+        while let Some(candidate) = current {
+            if candidate == self.origin {
+                break;
+            }
+            let ns: Vec<Record> = self
+                .lookup(candidate)
+                .iter()
+                .filter(|record| record.qtype() == QType::NS)
+                .cloned()
+                .collect();
+            if !ns.is_empty() {
+                // Keep walking up: the delegation closest to the apex wins,
+                // anything below it isn't ours to serve.
+                cut = Some(ns);
+            }
+            current = parent(candidate);
+        }
+        cut
+    }
+
+    /// The addresses of `host`, if it is in this zone
+    fn addresses(&self, host: &str) -> Vec<Record> {
+        self.lookup(host)
+            .iter()
+            .filter(|record| matches!(record.qtype(), QType::A | QType::AAAA))
+            .cloned()
+            .collect()
+    }
+
+    /// The SOA record as it goes into the authority section of a negative
+    /// answer: with the TTL lowered to the SOA minimum (RFC 2308 §3).
+    fn negative_soa(&self) -> Option<Record> {
+        let mut soa = self.soa()?.clone();
+        if let Record::SOA { minimum, ttl, .. } = &mut soa {
+            *ttl = (*ttl).min(*minimum);
+        }
+        Some(soa)
+    }
+
+    /// Fill in `response` with what this zone knows about `question`.
+    pub fn answer(&self, question: &Question, response: &mut Packet) {
+        response.header.authoritative = true;
+
+        let mut name = normalize(&question.name);
+        for _ in 0..MAX_CNAME_CHAIN {
+            if let Some(ns) = self.delegation(&name) {
+                // A referral: only answers we've collected so far are ours.
+                response.header.authoritative = !response.answers.is_empty();
+                for record in &ns {
+                    if let Record::NS { host, .. } = record {
+                        response.resources.extend(self.addresses(host));
+                    }
+                }
+                response.authorities.extend(ns);
+                return;
+            }
+
+            let records: Vec<&Record> = self
+                .lookup(&name)
+                .iter()
+                .filter(|record| {
+                    question.qclass == QClass::Any || record.class() == question.qclass
+                })
+                .collect();
+
+            if records.is_empty() && !self.name_exists(&name) {
+                response.header.rcode = RCode::NameError;
+                response.authorities.extend(self.negative_soa());
+                return;
+            }
+
+            let matching: Vec<Record> = records
+                .iter()
+                .filter(|record| question.qtype == QType::ANY || record.qtype() == question.qtype)
+                .map(|record| (*record).clone())
+                .collect();
+            if !matching.is_empty() {
+                self.add_additionals(&matching, response);
+                response.answers.extend(matching);
+                return;
+            }
+
+            let cname = records.iter().find_map(|record| match record {
+                Record::CNAME { host, .. } => Some((*record, host)),
+                _ => None,
+            });
+            match cname {
+                Some((record, host)) => {
+                    response.answers.push(record.clone());
+                    name = normalize(host);
+                    if !is_subdomain(&name, &self.origin) {
+                        // The resolver has to chase the rest itself.
+                        return;
+                    }
+                }
+                None => {
+                    // NODATA: the name exists, just not with this type.
+                    response.authorities.extend(self.negative_soa());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Add the addresses of the hosts named by NS, MX and SRV answers, so
+    /// the client doesn't have to look them up (RFC 1035 §3.3).
+    fn add_additionals(&self, answers: &[Record], response: &mut Packet) {
+        for record in answers {
+            let host = match record {
+                Record::NS { host, .. } | Record::MX { host, .. } => host,
+                Record::SRV { target, .. } => target,
+                _ => continue,
+            };
+            for address in self.addresses(host) {
+                if !response.resources.contains(&address) {
+                    response.resources.push(address);
+                }
+            }
+        }
+    }
+}
+
+/// The set of zones a server is authoritative for.
+#[derive(Debug, Clone, Default)]
+pub struct Authority {
+    zones: Vec<Zone>,
+}
+
+impl Authority {
+    pub fn new() -> Authority {
+        Authority::default()
+    }
+
+    pub fn add_zone(&mut self, zone: Zone) {
+        self.zones.retain(|existing| existing.origin != zone.origin);
+        self.zones.push(zone);
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// The most specific zone containing `name`
+    pub fn find_zone(&self, name: &str) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| is_subdomain(name.trim_end_matches('.'), &zone.origin))
+            .max_by_key(|zone| zone.origin.len())
+    }
+
+    pub fn find_zone_mut(&mut self, name: &str) -> Option<&mut Zone> {
+        self.zones
+            .iter_mut()
+            .filter(|zone| is_subdomain(name.trim_end_matches('.'), &zone.origin))
+            .max_by_key(|zone| zone.origin.len())
+    }
+}
+
+impl RequestHandler for Authority {
+    fn handle(&self, request: &Packet) -> Packet {
+        let mut response = response_to(request);
+
+        if request.header.opcode != Opcode::QUERY {
+            response.header.rcode = RCode::NotImplemented;
+            return response;
+        }
+        if request.questions.len() != 1 {
+            response.header.rcode = RCode::FormatError;
+            return response;
+        }
+
+        let question = &request.questions[0];
+        match self.find_zone(&question.name) {
+            Some(zone) => zone.answer(question, &mut response),
+            None => response.header.rcode = RCode::Refused,
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use std::net::Ipv4Addr;
+
+    fn a(domain: &str, addr: [u8; 4]) -> Record {
+        Record::A {
+            domain: domain.to_string(),
+            addr: Ipv4Addr::from(addr),
+            class: QClass::IN,
+            ttl: 300,
+        }
+    }
+
+    fn ns(domain: &str, host: &str) -> Record {
+        Record::NS {
+            domain: domain.to_string(),
+            host: host.to_string(),
+            class: QClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    fn cname(domain: &str, host: &str) -> Record {
+        Record::CNAME {
+            domain: domain.to_string(),
+            host: host.to_string(),
+            class: QClass::IN,
+            ttl: 300,
+        }
+    }
+
+    fn soa() -> Record {
+        Record::SOA {
+            domain: "example.com".to_string(),
+            mname: "ns1.example.com".to_string(),
+            rname: "hostmaster.example.com".to_string(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 60,
+            class: QClass::IN,
+            ttl: 3600,
+        }
+    }
+
+    fn authority() -> Authority {
+        let mut zone = Zone::new("example.com.");
+        zone.add(soa()).unwrap();
+        zone.add(ns("example.com", "ns1.example.com")).unwrap();
+        zone.add(a("ns1.example.com", [192, 0, 2, 53])).unwrap();
+        zone.add(a("www.example.com", [192, 0, 2, 1])).unwrap();
+        zone.add(cname("alias.example.com", "www.example.com"))
+            .unwrap();
+        zone.add(cname("external.example.com", "www.example.org"))
+            .unwrap();
+        zone.add(a("host.deep.example.com", [192, 0, 2, 2]))
+            .unwrap();
+        zone.add(Record::MX {
+            domain: "example.com".to_string(),
+            priority: 10,
+            host: "mail.example.com".to_string(),
+            class: QClass::IN,
+            ttl: 300,
+        })
+        .unwrap();
+        zone.add(a("mail.example.com", [192, 0, 2, 25])).unwrap();
+        zone.add(ns("sub.example.com", "ns.sub.example.com"))
+            .unwrap();
+        zone.add(a("ns.sub.example.com", [192, 0, 2, 54])).unwrap();
+
+        let mut authority = Authority::new();
+        authority.add_zone(zone);
+        authority
+    }
+
+    fn ask(authority: &Authority, name: &str, qtype: QType) -> Packet {
+        authority.handle(&Client::build_query(name, qtype))
+    }
+
+    #[test]
+    fn test_answer() {
+        let response = ask(&authority(), "WWW.example.com", QType::A);
+        assert!(response.header.authoritative);
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert_eq!(response.answers, vec![a("www.example.com", [192, 0, 2, 1])]);
+        assert!(response.authorities.is_empty());
+    }
+
+    #[test]
+    fn test_name_exists() {
+        let mut zone = Zone::new("example.com");
+        assert!(!zone.name_exists("example.com"));
+
+        zone.add(a("a.b.c.example.com", [192, 0, 2, 1])).unwrap();
+        for name in [
+            "a.b.c.example.com",
+            "B.c.example.com.",
+            "c.example.com",
+            "example.com",
+        ] {
+            assert!(zone.name_exists(name), "{}", name);
+        }
+        for name in ["x.a.b.c.example.com", "b.example.com", "com", ""] {
+            assert!(!zone.name_exists(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        let response = ask(&authority(), "nope.example.com", QType::A);
+        assert!(response.header.authoritative);
+        assert_eq!(response.header.rcode, RCode::NameError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities.len(), 1);
+        // The SOA's TTL is capped by its minimum field
+        assert_eq!(response.authorities[0].qtype(), QType::SOA);
+        assert_eq!(response.authorities[0].ttl(), 60);
+    }
+
+    #[test]
+    fn test_nodata() {
+        let authority = authority();
+        for (name, qtype) in [
+            ("www.example.com", QType::AAAA),
+            // An empty non-terminal
+            ("deep.example.com", QType::A),
+        ] {
+            let response = ask(&authority, name, qtype);
+            assert_eq!(response.header.rcode, RCode::NoError);
+            assert!(response.answers.is_empty());
+            assert_eq!(response.authorities[0].qtype(), QType::SOA);
+        }
+    }
+
+    #[test]
+    fn test_cname_chain() {
+        let response = ask(&authority(), "alias.example.com", QType::A);
+        assert_eq!(
+            response.answers,
+            vec![
+                cname("alias.example.com", "www.example.com"),
+                a("www.example.com", [192, 0, 2, 1]),
+            ]
+        );
+
+        let response = ask(&authority(), "external.example.com", QType::A);
+        assert_eq!(
+            response.answers,
+            vec![cname("external.example.com", "www.example.org")]
+        );
+        assert_eq!(response.header.rcode, RCode::NoError);
+    }
+
+    #[test]
+    fn test_additional_section() {
+        let response = ask(&authority(), "example.com", QType::MX);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(
+            response.resources,
+            vec![a("mail.example.com", [192, 0, 2, 25])]
+        );
+    }
+
+    #[test]
+    fn test_referral() {
+        let response = ask(&authority(), "www.sub.example.com", QType::A);
+        assert!(!response.header.authoritative);
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(
+            response.authorities,
+            vec![ns("sub.example.com", "ns.sub.example.com")]
+        );
+        assert_eq!(
+            response.resources,
+            vec![a("ns.sub.example.com", [192, 0, 2, 54])]
+        );
+    }
+
+    #[test]
+    fn test_refused_outside_our_zones() {
+        let response = ask(&authority(), "www.example.org", QType::A);
+        assert_eq!(response.header.rcode, RCode::Refused);
+        assert!(!response.header.authoritative);
+    }
+
+    #[test]
+    fn test_not_implemented() {
+        let mut request = Client::build_query("www.example.com", QType::A);
+        request.header.opcode = Opcode::UPDATE;
+        let response = authority().handle(&request);
+        assert_eq!(response.header.rcode, RCode::NotImplemented);
+    }
+
+    #[test]
+    fn test_rejects_records_outside_zone() {
+        let mut zone = Zone::new("example.com");
+        let record = a("www.example.org", [192, 0, 2, 1]);
+        assert_eq!(zone.add(record.clone()), Err(record));
+    }
+
+    #[test]
+    fn test_most_specific_zone() {
+        let mut authority = authority();
+        let mut zone = Zone::new("sub.example.com");
+        zone.add(a("www.sub.example.com", [192, 0, 2, 99])).unwrap();
+        authority.add_zone(zone);
+
+        let response = ask(&authority, "www.sub.example.com", QType::A);
+        assert!(response.header.authoritative);
+        assert_eq!(
+            response.answers,
+            vec![a("www.sub.example.com", [192, 0, 2, 99])]
+        );
+    }
+}
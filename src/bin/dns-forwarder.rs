@@ -0,0 +1,128 @@
+// A forwarding proxy which passes every query on to a pool of upstream
+// resolvers.
+//
+//     dns-forwarder [--listen ADDR] [--strategy first-healthy|round-robin|fastest]
+//                   [--timeout MS] [--blocklist PATH]... [--allowlist PATH]...
+//                   [--block-with nxdomain|null|refused] --upstream ADDR...
+//
+// An upstream without a port is asked on port 53. Queries for names in a
+// `--blocklist`, a hosts file or a list of domains, are answered without
+// asking upstream, unless the name is also in an `--allowlist`.
+use dns::blocklist::{BlockAction, Filter};
+use dns::forwarder::{Forwarder, Strategy};
+use dns::server;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "usage: dns-forwarder [--listen ADDR] \
+                     [--strategy first-healthy|round-robin|fastest] [--timeout MS] \
+                     [--blocklist PATH]... [--allowlist PATH]... \
+                     [--block-with nxdomain|null|refused] --upstream ADDR...";
+
+fn main() {
+    let (listen, filter) = match parse_args(std::env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match server::spawn(listen, Arc::new(filter)) {
+        Ok(addr) => println!("listening on {}", addr),
+        Err(err) => {
+            eprintln!("could not listen on {}: {}", listen, err);
+            process::exit(1);
+        }
+    }
+
+    loop {
+        thread::park();
+    }
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(SocketAddr, Filter<Forwarder>), String> {
+    let mut listen: SocketAddr = ([127, 0, 0, 1], 5353).into();
+    let mut upstreams = Vec::new();
+    let mut strategy = Strategy::default();
+    let mut timeout = None;
+    let mut blocklists = Vec::new();
+    let mut allowlists = Vec::new();
+    let mut action = BlockAction::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--listen" => {
+                let value = value()?;
+                listen = value
+                    .parse()
+                    .map_err(|_| format!("invalid address: {}", value))?;
+            }
+            "--upstream" => {
+                let value = value()?;
+                let upstream = match value.parse::<IpAddr>() {
+                    Ok(ip) => SocketAddr::new(ip, 53),
+                    Err(_) => value
+                        .parse()
+                        .map_err(|_| format!("invalid address: {}", value))?,
+                };
+                upstreams.push(upstream);
+            }
+            "--strategy" => {
+                strategy = match value()?.as_str() {
+                    "first-healthy" => Strategy::FirstHealthy,
+                    "round-robin" => Strategy::RoundRobin,
+                    "fastest" => Strategy::Fastest,
+                    other => return Err(format!("unknown strategy: {}", other)),
+                }
+            }
+            "--timeout" => {
+                let value = value()?;
+                let millis = value
+                    .parse()
+                    .map_err(|_| format!("invalid timeout: {}", value))?;
+                timeout = Some(Duration::from_millis(millis));
+            }
+            "--blocklist" => blocklists.push(value()?),
+            "--allowlist" => allowlists.push(value()?),
+            "--block-with" => {
+                action = match value()?.as_str() {
+                    "nxdomain" => BlockAction::NxDomain,
+                    "null" => BlockAction::NullAddress,
+                    "refused" => BlockAction::Refused,
+                    other => return Err(format!("unknown block action: {}", other)),
+                }
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+
+    if upstreams.is_empty() {
+        return Err("at least one --upstream is required".to_string());
+    }
+
+    let mut forwarder = Forwarder::new(upstreams, strategy);
+    if let Some(timeout) = timeout {
+        forwarder.timeout = timeout;
+    }
+
+    let mut filter = Filter::new(forwarder);
+    filter.action = action;
+    for (paths, set) in [
+        (blocklists, &mut filter.blocked),
+        (allowlists, &mut filter.allowed),
+    ] {
+        for path in paths {
+            set.add_file(Path::new(&path))
+                .map_err(|err| format!("{}: {}", path, err))?;
+        }
+    }
+    Ok((listen, filter))
+}
@@ -0,0 +1,222 @@
+// An authoritative-only nameserver for zones built from the command line.
+//
+//     dns-server [--listen ADDR] [--zone ORIGIN]... [--zone-file [ORIGIN=]PATH]...
+//                [--host NAME=IP]...
+//
+// Every `--zone` gets a synthesized SOA and NS record, and every `--host`
+// adds an A or AAAA record to the zone that contains it. A `--zone-file` is
+// a zone in the RFC 1035 master file format. As in a BIND config, ORIGIN
+// is what `@` and relative names in the file are relative to until a
+// `$ORIGIN`; without it the file has to set its own, and the zone's origin
+// is the owner of its SOA record.
+use dns::authority::{Authority, Zone};
+use dns::name::to_presentation;
+use dns::question::{QClass, QType};
+use dns::record::Record;
+use dns::server;
+use dns::zone_file;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+
+const USAGE: &str = "usage: dns-server [--listen ADDR] [--zone ORIGIN]... \
+                     [--zone-file [ORIGIN=]PATH]... \
+                     [--host NAME=IP]...";
+
+const TTL: u32 = 3600;
+
+fn main() {
+    let (listen, authority) = match parse_args(std::env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match server::spawn(listen, Arc::new(authority)) {
+        Ok(addr) => println!("listening on {}", addr),
+        Err(err) => {
+            eprintln!("could not listen on {}: {}", listen, err);
+            process::exit(1);
+        }
+    }
+
+    loop {
+        thread::park();
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(SocketAddr, Authority), String> {
+    let mut listen: SocketAddr = ([127, 0, 0, 1], 5353).into();
+    let mut zones: Vec<Zone> = Vec::new();
+    let mut hosts: Vec<(String, IpAddr)> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--listen" => {
+                let value = value()?;
+                listen = value
+                    .parse()
+                    .map_err(|_| format!("invalid address: {}", value))?;
+            }
+            "--zone" => zones.push(synthesize_zone(&value()?)),
+            "--zone-file" => {
+                let value = value()?;
+                let (origin, path) = value.split_once('=').unwrap_or(("", &value));
+                zones.push(load_zone(origin, Path::new(path))?);
+            }
+            "--host" => {
+                let value = value()?;
+                let (name, ip) = value
+                    .split_once('=')
+                    .ok_or(format!("expected NAME=IP, got {}", value))?;
+                let ip = ip
+                    .parse()
+                    .map_err(|_| format!("invalid IP address: {}", ip))?;
+                hosts.push((name.to_string(), ip));
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+
+    if zones.is_empty() {
+        return Err("at least one --zone or --zone-file is required".to_string());
+    }
+
+    let mut authority = Authority::new();
+    for zone in zones {
+        authority.add_zone(zone);
+    }
+    for (name, ip) in hosts {
+        let record = match ip {
+            IpAddr::V4(addr) => Record::A {
+                domain: name.clone(),
+                addr,
+                class: QClass::IN,
+                ttl: TTL,
+            },
+            IpAddr::V6(addr) => Record::AAAA {
+                domain: name.clone(),
+                addr,
+                class: QClass::IN,
+                ttl: TTL,
+            },
+        };
+        authority
+            .find_zone_mut(&name)
+            .ok_or(format!("{} is not in any --zone", name))?
+            .add(record)
+            .ok();
+    }
+
+    Ok((listen, authority))
+}
+
+/// A zone with just the records it needs to exist: an SOA and an NS record
+/// naming ourselves as `ns.ORIGIN`.
+fn synthesize_zone(origin: &str) -> Zone {
+    let mut zone = Zone::new(origin);
+    let nameserver = format!("ns.{}", zone.origin)
+        .trim_end_matches('.')
+        .to_string();
+    let hostmaster = format!("hostmaster.{}", zone.origin)
+        .trim_end_matches('.')
+        .to_string();
+
+    zone.add(Record::SOA {
+        domain: zone.origin.clone(),
+        mname: nameserver.clone(),
+        rname: hostmaster,
+        serial: 1,
+        refresh: 7200,
+        retry: 3600,
+        expire: 1209600,
+        minimum: 300,
+        class: QClass::IN,
+        ttl: TTL,
+    })
+    .ok();
+    zone.add(Record::NS {
+        domain: zone.origin.clone(),
+        host: nameserver,
+        class: QClass::IN,
+        ttl: TTL,
+    })
+    .ok();
+    zone
+}
+
+/// The zone in the master file at `path`, with names relative to `origin`.
+/// An empty `origin` leaves it to the file's `$ORIGIN`, and takes the zone
+/// to be rooted at its SOA record.
+fn load_zone(origin: &str, path: &Path) -> Result<Zone, String> {
+    let records = zone_file::parse_file(path, origin).map_err(|err| err.to_string())?;
+    let soa = records
+        .iter()
+        .find(|record| record.qtype() == QType::SOA)
+        .ok_or(format!("{}: no SOA record", path.display()))?;
+
+    // Without an origin, `@` and relative names end up at or just below the
+    // root, which is never what a zone file on its own means.
+    let hint = if origin.is_empty() {
+        " (without $ORIGIN, give the origin as --zone-file ORIGIN=PATH)"
+    } else {
+        ""
+    };
+    if origin.is_empty() && soa.domain().is_empty() {
+        return Err(format!(
+            "{}: SOA record for the root{}",
+            path.display(),
+            hint
+        ));
+    }
+
+    let mut zone = Zone::new(if origin.is_empty() {
+        soa.domain()
+    } else {
+        origin
+    });
+    for record in records {
+        zone.add(record).map_err(|record| {
+            format!(
+                "{}: {} is outside the zone {}{}",
+                path.display(),
+                to_presentation(record.domain()),
+                to_presentation(&zone.origin),
+                hint
+            )
+        })?;
+    }
+    Ok(zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_load_zone_origin() {
+        let path = env::temp_dir().join(format!("dns-server-test-{}.zone", process::id()));
+        fs::write(
+            &path,
+            "$TTL 300\n@ SOA ns hostmaster 1 2 3 4 60\n  NS ns\nns A 192.0.2.53\n",
+        )
+        .unwrap();
+
+        let zone = load_zone("example.com", &path).unwrap();
+        assert_eq!(zone.origin, "example.com");
+        assert_eq!(zone.soa().unwrap().domain(), "example.com");
+        assert_eq!(zone.lookup("ns.example.com").len(), 1);
+
+        let err = load_zone("", &path).unwrap_err();
+        assert!(err.contains("--zone-file ORIGIN=PATH"), "{}", err);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,433 @@
+// A dig-style query tool.
+//
+//     dnsq [@SERVER] [-p PORT] NAME [TYPE] [CLASS] [+tcp] [+[no]rec] [+edns]
+//          [+short] [+json]
+//
+// Without `@SERVER` the first nameserver in /etc/resolv.conf is asked.
+use dns::client::Client;
+use dns::edns::Opt;
+use dns::error::ClientError;
+use dns::name::to_presentation;
+use dns::packet::Packet;
+use dns::question::{QClass, QType};
+use dns::record::Record;
+use std::fs;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::process;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: dnsq [@SERVER] [-p PORT] NAME [TYPE] [CLASS] [+tcp] [+[no]rec] \
+                     [+edns] [+short] [+json]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Dig,
+    Short,
+    Json,
+}
+
+#[derive(Debug)]
+struct Options {
+    server: String,
+    port: u16,
+    name: String,
+    qtype: QType,
+    qclass: QClass,
+    tcp: bool,
+    recursion_desired: bool,
+    edns: bool,
+    output: Output,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let server = match (options.server.as_str(), options.port).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => addr,
+            None => fail(&format!("no address for server {}", options.server)),
+        },
+        Err(err) => fail(&format!(
+            "couldn't resolve server {}: {}",
+            options.server, err
+        )),
+    };
+
+    let mut request = Client::build_query(&options.name, options.qtype);
+    request.questions[0].qclass = options.qclass;
+    request.header.recursion_desired = options.recursion_desired;
+    if options.edns {
+        request.edns = Some(Opt::default());
+    }
+
+    let client = Client {
+        timeout: Duration::from_secs(5),
+        ..Client::new(server)
+    };
+    let start = Instant::now();
+    let result = if options.tcp {
+        client.query_tcp(&request)
+    } else {
+        client.query(&request)
+    };
+    let elapsed = start.elapsed();
+
+    let response = match result {
+        Ok(response) => response,
+        Err(ClientError::Timeout { .. }) => {
+            println!(";; connection timed out; no servers could be reached");
+            process::exit(9);
+        }
+        Err(err) => fail(&err.to_string()),
+    };
+
+    match options.output {
+        Output::Dig => print_dig(&options, server, &response, elapsed),
+        Output::Short => {
+            for record in &response.answers {
+                println!("{}", record.rdata_to_string());
+            }
+        }
+        Output::Json => println!("{}", to_json(&response)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!(";; {}", message);
+    process::exit(10);
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        server: default_server(),
+        port: 53,
+        name: String::new(),
+        qtype: QType::A,
+        qclass: QClass::IN,
+        tcp: false,
+        recursion_desired: true,
+        edns: false,
+        output: Output::Dig,
+    };
+
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        if let Some(server) = arg.strip_prefix('@') {
+            options.server = server.to_string();
+        } else if arg == "-p" {
+            let port = args.next().ok_or("-p needs a port")?;
+            options.port = port
+                .parse()
+                .map_err(|_| format!("invalid port: {}", port))?;
+        } else if let Some(flag) = arg.strip_prefix('+') {
+            match flag {
+                "tcp" | "vc" => options.tcp = true,
+                "notcp" | "novc" => options.tcp = false,
+                "rec" | "recurse" => options.recursion_desired = true,
+                "norec" | "norecurse" => options.recursion_desired = false,
+                "edns" => options.edns = true,
+                "noedns" => options.edns = false,
+                "short" => options.output = Output::Short,
+                "json" => options.output = Output::Json,
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    // dig lets the type and class come in any order after the name.
+    let mut positional = positional.into_iter();
+    options.name = positional.next().ok_or("missing name")?;
+    for arg in positional {
+        if let Some(qtype) = QType::from_mnemonic(&arg) {
+            options.qtype = qtype;
+        } else if let Some(qclass) = QClass::from_mnemonic(&arg) {
+            options.qclass = qclass;
+        } else {
+            return Err(format!("unknown type or class: {}", arg));
+        }
+    }
+
+    Ok(options)
+}
+
+/// The first nameserver in /etc/resolv.conf, or localhost without one
+fn default_server() -> String {
+    fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines().find_map(|line| {
+                let mut fields = line.split_whitespace();
+                match (fields.next(), fields.next()) {
+                    (Some("nameserver"), Some(server)) => Some(server.to_string()),
+                    _ => None,
+                }
+            })
+        })
+        .unwrap_or_else(|| "127.0.0.1".to_string())
+}
+
+/// The flags set in the header, by the names dig gives them
+fn flags(response: &Packet) -> Vec<&'static str> {
+    let header = &response.header;
+    [
+        (header.is_reply, "qr"),
+        (header.authoritative, "aa"),
+        (header.truncation, "tc"),
+        (header.recursion_desired, "rd"),
+        (header.recursion_available, "ra"),
+        (header.z, "z"),
+        (header.authentic_data, "ad"),
+        (header.checking_disabled, "cd"),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| name)
+    .collect()
+}
+
+/// The response code, including the extended codes of RFC 6891 §6.1.3
+fn status(response: &Packet) -> String {
+    match response.response_code() {
+        0..=15 => response.header.rcode.to_string(),
+        16 => "BADVERS".to_string(),
+        code => format!("RCODE{}", code),
+    }
+}
+
+fn print_dig(options: &Options, server: SocketAddr, response: &Packet, elapsed: Duration) {
+    let header = &response.header;
+    println!(
+        "; <<>> dnsq <<>> {} {} {}",
+        options.name, options.qtype, options.qclass
+    );
+    println!(";; Got answer:");
+    println!(
+        ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+        header.opcode,
+        status(response),
+        header.id
+    );
+    println!(
+        ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+        flags(response).join(" "),
+        header.question_count,
+        header.answer_count,
+        header.authority_count,
+        header.additional_count
+    );
+
+    if let Some(opt) = &response.edns {
+        println!();
+        println!(";; OPT PSEUDOSECTION:");
+        println!(
+            "; EDNS: version: {}, flags:{}; udp: {}",
+            opt.version,
+            if opt.dnssec_ok { " do" } else { "" },
+            opt.udp_payload_size
+        );
+    }
+
+    println!();
+    println!(";; QUESTION SECTION:");
+    for question in &response.questions {
+        println!(
+            ";{}\t\t{}\t{}",
+            to_presentation(&question.name),
+            question.qclass,
+            question.qtype
+        );
+    }
+
+    for (title, records) in [
+        ("ANSWER", &response.answers),
+        ("AUTHORITY", &response.authorities),
+        ("ADDITIONAL", &response.resources),
+    ] {
+        if records.is_empty() {
+            continue;
+        }
+        println!();
+        println!(";; {} SECTION:", title);
+        for record in records {
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                to_presentation(record.domain()),
+                record.ttl(),
+                record.class(),
+                record.qtype(),
+                record.rdata_to_string()
+            );
+        }
+    }
+
+    println!();
+    println!(";; Query time: {} msec", elapsed.as_millis());
+    println!(
+        ";; SERVER: {}#{}({}) ({})",
+        server.ip(),
+        server.port(),
+        options.server,
+        if options.tcp { "TCP" } else { "UDP" }
+    );
+}
+
+fn to_json(response: &Packet) -> String {
+    let header = &response.header;
+    let flags: Vec<String> = flags(response)
+        .iter()
+        .map(|flag| json_string(flag))
+        .collect();
+    let questions: Vec<String> = response
+        .questions
+        .iter()
+        .map(|question| {
+            format!(
+                "{{\"name\":{},\"type\":{},\"class\":{}}}",
+                json_string(&to_presentation(&question.name)),
+                json_string(&question.qtype.to_string()),
+                json_string(&question.qclass.to_string())
+            )
+        })
+        .collect();
+    let section = |records: &[Record]| -> String {
+        let records: Vec<String> = records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{{\"name\":{},\"ttl\":{},\"class\":{},\"type\":{},\"data\":{}}}",
+                    json_string(&to_presentation(record.domain())),
+                    record.ttl(),
+                    json_string(&record.class().to_string()),
+                    json_string(&record.qtype().to_string()),
+                    json_string(&record.rdata_to_string())
+                )
+            })
+            .collect();
+        format!("[{}]", records.join(","))
+    };
+
+    format!(
+        "{{\"id\":{},\"opcode\":{},\"status\":{},\"flags\":[{}],\"question\":[{}],\
+         \"answer\":{},\"authority\":{},\"additional\":{}}}",
+        header.id,
+        json_string(&header.opcode.to_string()),
+        json_string(&status(response)),
+        flags.join(","),
+        questions.join(","),
+        section(&response.answers),
+        section(&response.authorities),
+        section(&response.resources)
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dns::server::response_to;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse(&["codecrafters.io"]).unwrap();
+        assert_eq!(options.name, "codecrafters.io");
+        assert_eq!(options.qtype, QType::A);
+        assert_eq!(options.qclass, QClass::IN);
+        assert_eq!(options.port, 53);
+        assert!(options.recursion_desired);
+        assert_eq!(options.output, Output::Dig);
+
+        // The server, type and class may come in any order around the name
+        let options = parse(&["@192.0.2.53", "version.bind", "CH", "txt"]).unwrap();
+        assert_eq!(options.server, "192.0.2.53");
+        assert_eq!(options.name, "version.bind");
+        assert_eq!(options.qtype, QType::TXT);
+        assert_eq!(options.qclass, QClass::CH);
+
+        let options = parse(&["codecrafters.io", "MX", "-p", "5353", "@ns1"]).unwrap();
+        assert_eq!(options.server, "ns1");
+        assert_eq!(options.port, 5353);
+        assert_eq!(options.qtype, QType::MX);
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let options = parse(&["+tcp", "+norec", "codecrafters.io", "+edns", "+short"]).unwrap();
+        assert!(options.tcp);
+        assert!(!options.recursion_desired);
+        assert!(options.edns);
+        assert_eq!(options.output, Output::Short);
+
+        let options = parse(&["codecrafters.io", "+short", "+json"]).unwrap();
+        assert_eq!(options.output, Output::Json);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(&[]).unwrap_err(), "missing name");
+        assert_eq!(
+            parse(&["codecrafters.io", "+bogus"]).unwrap_err(),
+            "unknown option: +bogus"
+        );
+        assert_eq!(
+            parse(&["codecrafters.io", "NOPE"]).unwrap_err(),
+            "unknown type or class: NOPE"
+        );
+        assert_eq!(parse(&["-p", "x", "a"]).unwrap_err(), "invalid port: x");
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(json_string("\n\t\u{1}"), "\"\\n\\t\\u0001\"");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut response = response_to(&Client::build_query("codecrafters.io", QType::TXT));
+        response.header.id = 7;
+        response.answers.push(Record::TXT {
+            domain: "codecrafters.io".to_string(),
+            data: vec![b"say \"hi\" \\ \x01".to_vec()],
+            class: QClass::IN,
+            ttl: 60,
+        });
+
+        assert_eq!(
+            to_json(&response),
+            "{\"id\":7,\"opcode\":\"QUERY\",\"status\":\"NOERROR\",\"flags\":[\"qr\",\"rd\"],\
+             \"question\":[{\"name\":\"codecrafters.io.\",\"type\":\"TXT\",\"class\":\"IN\"}],\
+             \"answer\":[{\"name\":\"codecrafters.io.\",\"ttl\":60,\"class\":\"IN\",\
+             \"type\":\"TXT\",\"data\":\"\\\"say \\\\\\\"hi\\\\\\\" \\\\\\\\ \\\\001\\\"\"}],\
+             \"authority\":[],\"additional\":[]}"
+        );
+    }
+}
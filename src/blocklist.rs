@@ -0,0 +1,351 @@
+// Blocklists in the formats ad blocking DNS servers share: either a hosts
+// file, where every name after the address is blocked,
+//
+//     0.0.0.0 ads.example.com tracker.example.com
+//
+// or a plain list with one domain per line. `#` starts a comment in both.
+// Blocking a domain blocks every name below it too.
+use crate::header::RCode;
+use crate::name::normalize;
+use crate::packet::Packet;
+use crate::question::{QClass, QType};
+use crate::record::Record;
+use crate::server::{response_to, RequestHandler};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// Names hosts files map to themselves, which are never worth blocking
+const LOCAL_NAMES: [&str; 7] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "0.0.0.0",
+];
+
+/// A set of domains, each of which also covers the names below it, stored
+/// as a trie of labels from the root down so that matching a name takes one
+/// step per label, however many domains there are.
+#[derive(Debug, Clone, Default)]
+pub struct DomainSet {
+    root: Node,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    /// Whether the domain ending here is in the set
+    end: bool,
+}
+
+impl DomainSet {
+    pub fn new() -> DomainSet {
+        DomainSet::default()
+    }
+
+    /// Adds `domain`, returning false if it was already there or isn't a
+    /// domain name at all. The root isn't one here, as it would cover every
+    /// name.
+    pub fn insert(&mut self, domain: &str) -> bool {
+        let domain = normalize(domain);
+        if !is_domain(&domain) {
+            return false;
+        }
+        let mut node = &mut self.root;
+        for label in domain.rsplit('.').filter(|label| !label.is_empty()) {
+            node = node.children.entry(label.to_string()).or_default();
+        }
+
+        if node.end {
+            return false;
+        }
+        node.end = true;
+        self.len += 1;
+        true
+    }
+
+    /// Whether `name` or one of its ancestors is in the set
+    pub fn contains(&self, name: &str) -> bool {
+        let name = normalize(name);
+        let mut node = &self.root;
+        for label in name.rsplit('.').filter(|label| !label.is_empty()) {
+            if node.end {
+                return true;
+            }
+            match node.children.get(label) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        node.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds every domain in a hosts file or plain list, returning how many
+    /// were new. Entries that aren't valid domain names are skipped, since
+    /// published lists are rarely clean.
+    pub fn add_list(&mut self, text: &str) -> usize {
+        let mut added = 0;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace().peekable();
+            if let Some(first) = fields.peek() {
+                if first.parse::<IpAddr>().is_ok() {
+                    fields.next();
+                }
+            }
+
+            for domain in fields {
+                let domain = normalize(domain);
+                if !LOCAL_NAMES.contains(&domain.as_str()) && self.insert(&domain) {
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+
+    /// Adds every domain in the list at `path`, returning how many were new
+    pub fn add_file(&mut self, path: &Path) -> io::Result<usize> {
+        Ok(self.add_list(&fs::read_to_string(path)?))
+    }
+}
+
+/// Whether `name` looks like a host name: dot-separated labels of letters,
+/// digits, hyphens and underscores, within the RFC 1035 §2.3.4 limits
+fn is_domain(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
+/// How a `Filter` answers a query for a blocked name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockAction {
+    /// The name doesn't exist
+    #[default]
+    NxDomain,
+    /// The name exists at 0.0.0.0 and ::, so connections to it fail at once.
+    /// Other types, and classes other than IN, get an empty answer.
+    NullAddress,
+    /// The server won't answer
+    Refused,
+}
+
+/// A handler which answers queries for blocked names itself and passes
+/// everything else on to `inner`.
+#[derive(Debug)]
+pub struct Filter<H> {
+    pub inner: H,
+    pub blocked: DomainSet,
+    /// Names which are never blocked, even when they are in `blocked`
+    pub allowed: DomainSet,
+    pub action: BlockAction,
+    /// The TTL of null addresses
+    pub ttl: u32,
+}
+
+impl<H: RequestHandler> Filter<H> {
+    pub fn new(inner: H) -> Filter<H> {
+        Filter {
+            inner,
+            blocked: DomainSet::new(),
+            allowed: DomainSet::new(),
+            action: BlockAction::default(),
+            ttl: 60,
+        }
+    }
+
+    pub fn is_blocked(&self, name: &str) -> bool {
+        self.blocked.contains(name) && !self.allowed.contains(name)
+    }
+
+    fn block(&self, request: &Packet) -> Packet {
+        let mut response = response_to(request);
+        response.header.recursion_available = true;
+
+        match self.action {
+            BlockAction::NxDomain => response.header.rcode = RCode::NameError,
+            BlockAction::Refused => response.header.rcode = RCode::Refused,
+            BlockAction::NullAddress => {
+                let question = &request.questions[0];
+                let domain = question.name.clone();
+                let (class, ttl) = (question.qclass, self.ttl);
+                match (class, question.qtype) {
+                    (QClass::IN, QType::A) => response.answers.push(Record::A {
+                        domain,
+                        addr: Ipv4Addr::UNSPECIFIED,
+                        class,
+                        ttl,
+                    }),
+                    (QClass::IN, QType::AAAA) => response.answers.push(Record::AAAA {
+                        domain,
+                        addr: Ipv6Addr::UNSPECIFIED,
+                        class,
+                        ttl,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        response
+    }
+}
+
+impl<H: RequestHandler> RequestHandler for Filter<H> {
+    fn handle(&self, request: &Packet) -> Packet {
+        match request.questions.as_slice() {
+            [question] if self.is_blocked(&question.name) => self.block(request),
+            _ => self.inner.handle(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+
+    const LIST: &str = "\
+# A hosts file
+127.0.0.1 localhost
+::1 localhost ip6-localhost
+0.0.0.0 ads.example.com tracker.example.net # trailing comment
+0.0.0.0  Doubleclick.NET.
+
+# A plain list
+metrics.example.org
+ads/example.com
+";
+
+    /// Answers everything with an A record for 192.0.2.1
+    struct Upstream;
+
+    impl RequestHandler for Upstream {
+        fn handle(&self, request: &Packet) -> Packet {
+            let mut response = response_to(request);
+            response.answers.push(Record::A {
+                domain: request.questions[0].name.clone(),
+                addr: Ipv4Addr::new(192, 0, 2, 1),
+                class: QClass::IN,
+                ttl: 300,
+            });
+            response
+        }
+    }
+
+    #[test]
+    fn test_domain_set() {
+        let mut set = DomainSet::new();
+        assert!(set.insert("example.com"));
+        assert!(!set.insert("EXAMPLE.com."));
+        assert_eq!(set.len(), 1);
+
+        // The root would cover everything
+        for invalid in ["", ".", "ads..example.com", "ads/example.com"] {
+            assert!(!set.insert(invalid), "{:?}", invalid);
+        }
+        assert_eq!(set.len(), 1);
+        assert!(!set.contains("example.org"));
+
+        assert!(set.contains("example.com"));
+        assert!(set.contains("www.Example.com."));
+        assert!(set.contains("a.b.example.com"));
+        assert!(!set.contains("com"));
+        assert!(!set.contains("notexample.com"));
+        assert!(!set.contains("example.org"));
+    }
+
+    #[test]
+    fn test_add_list() {
+        let mut set = DomainSet::new();
+        assert_eq!(set.add_list(LIST), 4);
+        assert_eq!(set.add_list(LIST), 0);
+
+        assert!(set.contains("ads.example.com"));
+        assert!(set.contains("tracker.example.net"));
+        assert!(set.contains("stats.g.doubleclick.net"));
+        assert!(set.contains("metrics.example.org"));
+        assert!(!set.contains("localhost"));
+        assert!(!set.contains("example.com"));
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut filter = Filter::new(Upstream);
+        filter.blocked.add_list(LIST);
+        filter.allowed.insert("safe.ads.example.com");
+
+        let request = Client::build_query("www.ads.example.com", QType::A);
+        let response = filter.handle(&request);
+        assert_eq!(response.header.id, request.header.id);
+        assert_eq!(response.header.rcode, RCode::NameError);
+        assert!(response.answers.is_empty());
+
+        // Allowed names, and those below them, are passed on
+        for name in [
+            "example.com",
+            "safe.ads.example.com",
+            "cdn.safe.ads.example.com",
+        ] {
+            let response = filter.handle(&Client::build_query(name, QType::A));
+            assert_eq!(response.header.rcode, RCode::NoError);
+            assert_eq!(response.answers.len(), 1);
+        }
+
+        filter.action = BlockAction::Refused;
+        let response = filter.handle(&request);
+        assert_eq!(response.header.rcode, RCode::Refused);
+    }
+
+    #[test]
+    fn test_null_address() {
+        let mut filter = Filter::new(Upstream);
+        filter.blocked.insert("doubleclick.net");
+        filter.action = BlockAction::NullAddress;
+
+        let response = filter.handle(&Client::build_query("ad.doubleclick.net", QType::A));
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(matches!(
+            response.answers.as_slice(),
+            [Record::A { addr, ttl: 60, .. }] if addr.is_unspecified()
+        ));
+
+        let response = filter.handle(&Client::build_query("ad.doubleclick.net", QType::AAAA));
+        assert!(matches!(
+            response.answers.as_slice(),
+            [Record::AAAA { addr, .. }] if addr.is_unspecified()
+        ));
+
+        let response = filter.handle(&Client::build_query("ad.doubleclick.net", QType::MX));
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+
+        // Addresses are only for the Internet class
+        let mut request = Client::build_query("ad.doubleclick.net", QType::A);
+        request.questions[0].qclass = QClass::CH;
+        let response = filter.handle(&request);
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+    }
+}
@@ -1,6 +1,20 @@
+use crate::error::{ParseError, WriteError};
+use std::collections::HashMap;
+
+/// The size of a classic UDP DNS message (RFC 1035 §4.2.1).
+pub const DEFAULT_SIZE: usize = 512;
+
+/// The largest message that can be expressed with the two-byte TCP length
+/// prefix, and therefore the largest message this buffer will ever hold.
+pub const MAX_SIZE: usize = 65535;
+
 pub struct BytePacketBuffer {
-    pub buf: [u8; 512],
+    pub buf: Vec<u8>,
     pub pos: usize,
+    max_size: usize,
+    /// Offsets of name suffixes written so far, keyed by their lowercased
+    /// text, used to emit compression pointers (RFC 1035 §4.1.4).
+    names: HashMap<String, usize>,
 }
 
 impl Default for BytePacketBuffer {
@@ -13,35 +27,77 @@ impl BytePacketBuffer {
     /// This gives us a fresh buffer for holding the packet contents, and a
     /// field for keeping track of where we are.
     pub fn new() -> BytePacketBuffer {
+        BytePacketBuffer::with_capacity(DEFAULT_SIZE)
+    }
+
+    /// A zeroed buffer of `size` bytes which will not grow past that size.
+    /// Useful as a receive buffer when the expected message size is known up
+    /// front, e.g. the advertised EDNS0 payload size.
+    pub fn with_capacity(size: usize) -> BytePacketBuffer {
+        let size = size.min(MAX_SIZE);
+        BytePacketBuffer {
+            buf: vec![0; size],
+            pos: 0,
+            max_size: size,
+            names: HashMap::new(),
+        }
+    }
+
+    /// An empty buffer which grows as it is written to, up to `max_size`
+    /// bytes.
+    pub fn with_max_size(max_size: usize) -> BytePacketBuffer {
+        BytePacketBuffer {
+            buf: Vec::new(),
+            pos: 0,
+            max_size: max_size.min(MAX_SIZE),
+            names: HashMap::new(),
+        }
+    }
+
+    /// A buffer holding a copy of a received message, positioned at its
+    /// start and ready to be parsed.
+    pub fn from_bytes(bytes: &[u8]) -> BytePacketBuffer {
         BytePacketBuffer {
-            buf: [0; 512],
+            buf: bytes[..bytes.len().min(MAX_SIZE)].to_vec(),
             pos: 0,
+            max_size: MAX_SIZE,
+            names: HashMap::new(),
         }
     }
 
+    /// The largest number of bytes this buffer may hold
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// The bytes written so far, i.e. everything before the current position
+    pub fn filled(&self) -> &[u8] {
+        &self.buf[..self.pos.min(self.buf.len())]
+    }
+
     /// Current position within buffer
     pub fn pos(&self) -> usize {
         self.pos
     }
 
     /// Step the buffer position forward a specific number of steps
-    pub fn step(&mut self, steps: usize) -> anyhow::Result<()> {
+    pub fn step(&mut self, steps: usize) -> Result<(), ParseError> {
         self.pos += steps;
 
         Ok(())
     }
 
     /// Change the buffer position
-    pub fn seek(&mut self, pos: usize) -> anyhow::Result<()> {
+    pub fn seek(&mut self, pos: usize) -> Result<(), ParseError> {
         self.pos = pos;
 
         Ok(())
     }
 
     /// Read a single byte and move the position one step forward
-    pub fn read(&mut self) -> anyhow::Result<u8> {
-        if self.pos >= 512 {
-            anyhow::bail!("End of buffer");
+    pub fn read(&mut self) -> Result<u8, ParseError> {
+        if self.pos >= self.buf.len() {
+            return Err(ParseError::UnexpectedEnd { offset: self.pos });
         }
         let res = self.buf[self.pos];
         self.pos += 1;
@@ -50,30 +106,32 @@ impl BytePacketBuffer {
     }
 
     /// Get a single byte, without changing the buffer position
-    pub fn get(&mut self, pos: usize) -> anyhow::Result<u8> {
-        if pos >= 512 {
-            anyhow::bail!("End of buffer");
+    pub fn get(&mut self, pos: usize) -> Result<u8, ParseError> {
+        if pos >= self.buf.len() {
+            return Err(ParseError::UnexpectedEnd { offset: pos });
         }
         Ok(self.buf[pos])
     }
 
     /// Get a range of bytes
-    pub fn get_range(&mut self, start: usize, len: usize) -> anyhow::Result<&[u8]> {
-        if start + len >= 512 {
-            anyhow::bail!("End of buffer");
+    pub fn get_range(&mut self, start: usize, len: usize) -> Result<&[u8], ParseError> {
+        if start + len > self.buf.len() {
+            return Err(ParseError::UnexpectedEnd {
+                offset: self.buf.len().max(start),
+            });
         }
         Ok(&self.buf[start..start + len])
     }
 
     /// Read two bytes, stepping two steps forward
-    pub fn read_u16(&mut self) -> anyhow::Result<u16> {
+    pub fn read_u16(&mut self) -> Result<u16, ParseError> {
         let res = ((self.read()? as u16) << 8) | (self.read()? as u16);
 
         Ok(res)
     }
 
     /// Read four bytes, stepping four steps forward
-    pub fn read_u32(&mut self) -> anyhow::Result<u32> {
+    pub fn read_u32(&mut self) -> Result<u32, ParseError> {
         let res = ((self.read()? as u32) << 24)
             | ((self.read()? as u32) << 16)
             | ((self.read()? as u32) << 8)
@@ -83,13 +141,18 @@ impl BytePacketBuffer {
     }
 
     /// Read a qname
-    pub fn read_qname(&mut self, outstr: &mut String) -> anyhow::Result<()> {
+    pub fn read_qname(&mut self, outstr: &mut String) -> Result<(), ParseError> {
         // Since we might encounter jumps, we'll keep track of our position
         // locally as opposed to using the position within the struct. This
         // allows us to move the shared position to a point past our current
         // qname, while keeping track of our progress on the current qname
         // using this variable.
         let mut pos = self.pos();
+        let start = pos;
+
+        // Names are limited to 255 bytes in their uncompressed wire form,
+        // counting every length byte including the terminating zero.
+        let mut name_len = 1;
 
         // track whether or not we've jumped
         let mut jumped = false;
@@ -105,7 +168,10 @@ impl BytePacketBuffer {
             // can craft a packet with a cycle in the jump instructions. This guards
             // against such packets.
             if jumps_performed > max_jumps {
-                anyhow::bail!("Limit of {} jumps exceeded", max_jumps);
+                return Err(ParseError::TooManyJumps {
+                    offset: start,
+                    limit: max_jumps,
+                });
             }
 
             // At this point, we're always at the beginning of a label. Recall
@@ -133,6 +199,13 @@ impl BytePacketBuffer {
 
                 continue;
             }
+            // 0b01 and 0b10 are reserved label types which we can't interpret.
+            else if (len & 0xC0) != 0 {
+                return Err(ParseError::InvalidLabel {
+                    offset: pos,
+                    byte: len,
+                });
+            }
             // The base scenario, where we're reading a single label and
             // appending it to the output:
             else {
@@ -145,6 +218,11 @@ impl BytePacketBuffer {
                     break;
                 }
 
+                name_len += len as usize + 1;
+                if name_len > 255 {
+                    return Err(ParseError::NameTooLong { offset: start });
+                }
+
                 // Append the delimiter to our output buffer first.
                 outstr.push_str(delim);
 
@@ -167,29 +245,58 @@ impl BytePacketBuffer {
         Ok(())
     }
 
-    pub fn write(&mut self, val: u8) -> anyhow::Result<()> {
-        if self.pos >= 512 {
-            anyhow::bail!("End of buffer");
+    pub fn write(&mut self, val: u8) -> Result<(), WriteError> {
+        if self.pos >= self.max_size {
+            return Err(WriteError::BufferFull {
+                offset: self.pos,
+                max_size: self.max_size,
+            });
+        }
+        if self.pos >= self.buf.len() {
+            self.buf.resize(self.pos + 1, 0);
         }
         self.buf[self.pos] = val;
         self.pos += 1;
         Ok(())
     }
 
-    pub fn write_u8(&mut self, val: u8) -> anyhow::Result<()> {
+    /// Overwrite a byte that has already been written, without changing the
+    /// buffer position
+    pub fn set(&mut self, pos: usize, val: u8) -> Result<(), WriteError> {
+        if pos >= self.buf.len() {
+            return Err(WriteError::BufferFull {
+                offset: pos,
+                max_size: self.max_size,
+            });
+        }
+        self.buf[pos] = val;
+
+        Ok(())
+    }
+
+    /// Overwrite two bytes that have already been written, e.g. to fill in
+    /// a length once the data it covers is known
+    pub fn set_u16(&mut self, pos: usize, val: u16) -> Result<(), WriteError> {
+        self.set(pos, (val >> 8) as u8)?;
+        self.set(pos + 1, (val & 0xFF) as u8)?;
+
+        Ok(())
+    }
+
+    pub fn write_u8(&mut self, val: u8) -> Result<(), WriteError> {
         self.write(val)?;
 
         Ok(())
     }
 
-    pub fn write_u16(&mut self, val: u16) -> anyhow::Result<()> {
+    pub fn write_u16(&mut self, val: u16) -> Result<(), WriteError> {
         self.write((val >> 8) as u8)?;
         self.write((val & 0xFF) as u8)?;
 
         Ok(())
     }
 
-    pub fn write_u32(&mut self, val: u32) -> anyhow::Result<()> {
+    pub fn write_u32(&mut self, val: u32) -> Result<(), WriteError> {
         self.write(((val >> 24) & 0xFF) as u8)?;
         self.write(((val >> 16) & 0xFF) as u8)?;
         self.write(((val >> 8) & 0xFF) as u8)?;
@@ -198,23 +305,86 @@ impl BytePacketBuffer {
         Ok(())
     }
 
-    pub fn write_qname(&mut self, qname: &str) -> anyhow::Result<()> {
-        for label in qname.split('.') {
-            let len = label.len();
-            if len > 0x3f {
-                anyhow::bail!("Single label exceeds 63 characters of length");
+    /// Write a qname, replacing the longest suffix that has already been
+    /// written to this buffer with a compression pointer to it.
+    pub fn write_qname(&mut self, qname: &str) -> Result<(), WriteError> {
+        let labels = Self::labels(qname)?;
+
+        // The suffixes only become something to point at once the whole
+        // name is written: a name cut short by a full buffer must not be
+        // pointed at by whatever is written after it.
+        let mut written = Vec::new();
+        let mut pointer = None;
+        for (i, label) in labels.iter().enumerate() {
+            let suffix = labels[i..].join(".").to_lowercase();
+            if let Some(&offset) = self.names.get(&suffix) {
+                pointer = Some(offset);
+                break;
             }
 
-            self.write_u8(len as u8)?;
-            for b in label.as_bytes() {
-                self.write_u8(*b)?;
+            // Pointers only have 14 bits of offset, so names written any
+            // further into the message can't be pointed at.
+            let pos = self.pos();
+            if pos < 0x4000 {
+                written.push((suffix, pos));
             }
+
+            self.write_label(label)?;
+        }
+
+        match pointer {
+            Some(offset) => self.write_u16(0xC000 | offset as u16)?,
+            None => self.write_u8(0)?,
+        }
+        self.names.extend(written);
+
+        Ok(())
+    }
+
+    /// Write a qname as a full label sequence. Some RDATA, e.g. the SRV
+    /// target, must never be compressed.
+    pub fn write_qname_uncompressed(&mut self, qname: &str) -> Result<(), WriteError> {
+        for label in Self::labels(qname)? {
+            self.write_label(label)?;
         }
 
         self.write_u8(0)?;
 
         Ok(())
     }
+
+    /// Split a name into its labels. Both "" and "." are the root name, which
+    /// has no labels.
+    fn labels(qname: &str) -> Result<Vec<&str>, WriteError> {
+        let qname = qname.strip_suffix('.').unwrap_or(qname);
+        if qname.is_empty() {
+            return Ok(Vec::new());
+        }
+        // One length byte per label and the terminating zero, which makes
+        // the encoded name two bytes longer than its dotted text.
+        if qname.len() + 2 > 255 {
+            return Err(WriteError::NameTooLong {
+                name: qname.to_string(),
+            });
+        }
+        Ok(qname.split('.').collect())
+    }
+
+    fn write_label(&mut self, label: &str) -> Result<(), WriteError> {
+        let len = label.len();
+        if len > 0x3f {
+            return Err(WriteError::LabelTooLong {
+                label: label.to_string(),
+            });
+        }
+
+        self.write_u8(len as u8)?;
+        for b in label.as_bytes() {
+            self.write_u8(*b)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -251,6 +421,19 @@ mod tests {
         assert_eq!(packet.pos(), 4);
     }
 
+    #[test]
+    fn test_set_u16() {
+        let mut packet = BytePacketBuffer::new();
+        packet.write_u16(0).unwrap();
+        packet.write_u8(0xFF).unwrap();
+        packet.set_u16(0, 0x1234).unwrap();
+        assert_eq!(packet.filled(), [0x12, 0x34, 0xFF]);
+        assert_eq!(packet.pos(), 3);
+
+        let mut packet = BytePacketBuffer::with_max_size(16);
+        assert!(packet.set_u16(0, 0x1234).is_err());
+    }
+
     #[test]
     fn test_read_write_qname() {
         let mut packet = BytePacketBuffer::new();
@@ -279,6 +462,188 @@ mod tests {
         assert_eq!(outstr, "www.google.com");
     }
 
+    #[test]
+    fn test_default_buffer_is_limited_to_512_bytes() {
+        let mut packet = BytePacketBuffer::new();
+        assert_eq!(packet.max_size(), DEFAULT_SIZE);
+        packet.seek(511).unwrap();
+        packet.write_u8(1).unwrap();
+        assert!(packet.write_u8(2).is_err());
+        assert!(packet.read().is_err());
+    }
+
+    #[test]
+    fn test_growable_buffer() {
+        let mut packet = BytePacketBuffer::with_max_size(1024);
+        assert_eq!(packet.buf.len(), 0);
+        for i in 0..1024 {
+            packet.write_u8((i % 256) as u8).unwrap();
+        }
+        assert_eq!(packet.buf.len(), 1024);
+        assert_eq!(packet.filled().len(), 1024);
+        assert!(packet.write_u8(0).is_err());
+
+        packet.seek(600).unwrap();
+        assert_eq!(packet.read().unwrap(), (600 % 256) as u8);
+    }
+
+    #[test]
+    fn test_max_size_is_capped() {
+        let packet = BytePacketBuffer::with_max_size(usize::MAX);
+        assert_eq!(packet.max_size(), MAX_SIZE);
+        let packet = BytePacketBuffer::with_capacity(100_000);
+        assert_eq!(packet.buf.len(), MAX_SIZE);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let mut bytes = vec![0; 1000];
+        bytes[997] = 0x12;
+        bytes[998] = 0x34;
+        let mut packet = BytePacketBuffer::from_bytes(&bytes);
+        packet.seek(997).unwrap();
+        assert_eq!(packet.read_u16().unwrap(), 0x1234);
+        assert_eq!(packet.get_range(990, 10).unwrap().len(), 10);
+        assert!(packet.get_range(990, 11).is_err());
+    }
+
+    #[test]
+    fn test_write_root_qname() {
+        let mut packet = BytePacketBuffer::new();
+        packet.write_qname("").unwrap();
+        packet.write_qname(".").unwrap();
+        assert_eq!(packet.filled(), [0, 0]);
+    }
+
+    #[test]
+    fn test_write_compressed_qname() {
+        let mut packet = BytePacketBuffer::new();
+        packet.write_qname("f.isi.arpa").unwrap();
+        packet.write_qname("foo.f.isi.arpa").unwrap();
+        packet.write_qname("arpa").unwrap();
+        packet.write_qname("ISI.Arpa").unwrap();
+        assert_eq!(
+            packet.filled(),
+            [
+                1, b'f', 3, b'i', b's', b'i', 4, b'a', b'r', b'p', b'a', 0, // f.isi.arpa
+                3, b'f', b'o', b'o', 0xC0, 0, // foo + pointer to f.isi.arpa
+                0xC0, 6, // pointer to arpa
+                0xC0, 2, // pointer to isi.arpa
+            ]
+        );
+
+        let mut outstr = String::new();
+        packet.seek(12).unwrap();
+        packet.read_qname(&mut outstr).unwrap();
+        assert_eq!(outstr, "foo.f.isi.arpa");
+        assert_eq!(packet.pos(), 18);
+    }
+
+    #[test]
+    fn test_write_qname_uncompressed() {
+        let mut packet = BytePacketBuffer::new();
+        packet.write_qname("isi.arpa").unwrap();
+        packet.write_qname_uncompressed("isi.arpa").unwrap();
+        assert_eq!(packet.pos(), 20);
+        assert_eq!(packet.buf[..10], packet.buf[10..20]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut packet = BytePacketBuffer::from_bytes(&[0x12]);
+        assert_eq!(
+            packet.read_u16(),
+            Err(ParseError::UnexpectedEnd { offset: 1 })
+        );
+
+        let mut packet = BytePacketBuffer::from_bytes(&[3, b'w', b'w']);
+        let mut outstr = String::new();
+        assert_eq!(
+            packet.read_qname(&mut outstr),
+            Err(ParseError::UnexpectedEnd { offset: 3 })
+        );
+
+        let mut packet = BytePacketBuffer::from_bytes(&[0, 0, 0x40, 0]);
+        packet.seek(2).unwrap();
+        assert_eq!(
+            packet.read_qname(&mut outstr),
+            Err(ParseError::InvalidLabel {
+                offset: 2,
+                byte: 0x40
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_qname_pointer_loop() {
+        let mut packet = BytePacketBuffer::from_bytes(&[0, 0, 0xC0, 2]);
+        packet.seek(2).unwrap();
+        let mut outstr = String::new();
+        assert_eq!(
+            packet.read_qname(&mut outstr),
+            Err(ParseError::TooManyJumps {
+                offset: 2,
+                limit: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_qname_too_long() {
+        let mut bytes = Vec::new();
+        for _ in 0..5 {
+            bytes.push(63);
+            bytes.extend_from_slice(&[b'a'; 63]);
+        }
+        bytes.push(0);
+        let mut packet = BytePacketBuffer::from_bytes(&bytes);
+        let mut outstr = String::new();
+        assert_eq!(
+            packet.read_qname(&mut outstr),
+            Err(ParseError::NameTooLong { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let mut packet = BytePacketBuffer::with_capacity(1);
+        packet.write_u8(1).unwrap();
+        assert_eq!(
+            packet.write_u8(2),
+            Err(WriteError::BufferFull {
+                offset: 1,
+                max_size: 1
+            })
+        );
+
+        let label = "a".repeat(64);
+        let mut packet = BytePacketBuffer::new();
+        assert_eq!(
+            packet.write_qname(&label),
+            Err(WriteError::LabelTooLong { label })
+        );
+
+        let name = vec!["a".repeat(63); 4].join(".");
+        assert_eq!(
+            packet.write_qname(&name),
+            Err(WriteError::NameTooLong { name })
+        );
+    }
+
+    #[test]
+    fn test_no_pointers_to_unfinished_names() {
+        // One byte short of "abc.example.com"
+        let mut packet = BytePacketBuffer::with_capacity(16);
+        assert!(matches!(
+            packet.write_qname("abc.example.com"),
+            Err(WriteError::BufferFull { .. })
+        ));
+
+        packet.seek(0).unwrap();
+        packet.write_qname("com").unwrap();
+        assert_eq!(packet.filled(), [3, b'c', b'o', b'm', 0]);
+    }
+
     #[test]
     fn test_read_compressed_qname() {
         let mut packet = BytePacketBuffer::new();
@@ -0,0 +1,553 @@
+use crate::header::RCode;
+use crate::name::normalize;
+use crate::packet::Packet;
+use crate::question::{QClass, QType};
+use crate::record::Record;
+use crate::server::response_to;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// The longest we keep anything, however long its TTL, so that a mistake in
+/// a zone doesn't stick around for decades (RFC 8767 §4 suggests 7 days).
+pub const MAX_TTL: u32 = 7 * 24 * 60 * 60;
+
+/// The longest we remember that something doesn't exist, as RFC 2308 §5
+/// recommends
+pub const MAX_NEGATIVE_TTL: u32 = 3 * 60 * 60;
+
+/// What the cache is keyed by: an owner name in the form `read_qname`
+/// produces, a type and a class. Without a type, the key is for the
+/// nonexistence of the name as a whole.
+type CacheKey = (String, Option<QType>, QClass);
+
+/// What the cache knows about a name, type and class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cached {
+    /// The record set, with TTLs counted down
+    Records(Vec<Record>),
+    /// The name exists but has no records of the type (NODATA). The SOA of
+    /// the zone that said so has the time left until the entry expires as
+    /// its TTL.
+    NoData { soa: Record },
+    /// The name doesn't exist, with any type (NXDOMAIN), with the SOA as
+    /// for `NoData`.
+    NxDomain { soa: Record },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    cached: Cached,
+    stored: Instant,
+    expires: Instant,
+    /// When the entry was last used, as a tick of the cache's clock. Also
+    /// its key in `Cache::lru`.
+    used: u64,
+}
+
+/// A cache of record sets which hands them out with their TTLs counting
+/// down, and forgets them when they expire. When full, the least recently
+/// used set makes room for a new one.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    entries: HashMap<CacheKey, Entry>,
+    /// The keys of `entries` by when they were last used, oldest first
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    /// A cache holding at most `capacity` record sets
+    pub fn new(capacity: usize) -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            capacity,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Store `records`, as one set per owner name, type and class. Each set
+    /// replaces whatever was cached for its key, and expires with its
+    /// record with the lowest TTL. Records with a TTL of 0 are only meant
+    /// for the transaction that fetched them, so they aren't stored.
+    pub fn insert(&mut self, records: &[Record]) {
+        let now = Instant::now();
+        self.insert_at(records, now);
+    }
+
+    fn insert_at(&mut self, records: &[Record], now: Instant) {
+        let mut sets: HashMap<CacheKey, Vec<Record>> = HashMap::new();
+        for record in records {
+            let key = (
+                normalize(record.domain()),
+                Some(record.qtype()),
+                record.class(),
+            );
+            let set = sets.entry(key).or_default();
+            if !set.contains(record) {
+                set.push(record.clone());
+            }
+        }
+
+        for (key, records) in sets {
+            let ttl = records
+                .iter()
+                .map(Record::ttl)
+                .min()
+                .unwrap_or(0)
+                .min(MAX_TTL);
+            if ttl == 0 {
+                continue;
+            }
+            // The name evidently exists now.
+            self.remove(&(key.0.clone(), None, key.2));
+            self.store(key, Cached::Records(records), now, ttl);
+        }
+    }
+
+    /// Remember that `name` doesn't exist, as the authority section of an
+    /// NXDOMAIN response with `soa` in it says. This covers every type.
+    pub fn insert_nxdomain(&mut self, name: &str, qclass: QClass, soa: &Record) {
+        self.insert_negative((normalize(name), None, qclass), soa, Instant::now());
+    }
+
+    /// Remember that `name` has no records of `qtype`, as the authority
+    /// section of a NODATA response with `soa` in it says.
+    pub fn insert_nodata(&mut self, name: &str, qtype: QType, qclass: QClass, soa: &Record) {
+        self.insert_negative((normalize(name), Some(qtype), qclass), soa, Instant::now());
+    }
+
+    /// A negative answer lives as long as the SOA's own TTL or its minimum
+    /// field, whichever is lower (RFC 2308 §5).
+    fn insert_negative(&mut self, key: CacheKey, soa: &Record, now: Instant) {
+        let Record::SOA { minimum, .. } = soa else {
+            return;
+        };
+        let ttl = soa.ttl().min(*minimum).min(MAX_NEGATIVE_TTL);
+        if ttl == 0 {
+            return;
+        }
+
+        let mut soa = soa.clone();
+        soa.set_ttl(ttl);
+        let cached = match key.1 {
+            Some(_) => Cached::NoData { soa },
+            None => Cached::NxDomain { soa },
+        };
+        self.store(key, cached, now, ttl);
+    }
+
+    fn store(&mut self, key: CacheKey, cached: Cached, now: Instant, ttl: u32) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        if self.entries.len() >= self.capacity {
+            self.purge_expired_at(now);
+        }
+        while self.entries.len() >= self.capacity {
+            match self.lru.pop_first() {
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        self.tick += 1;
+        self.lru.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                cached,
+                stored: now,
+                expires: now + Duration::from_secs(ttl as u64),
+                used: self.tick,
+            },
+        );
+    }
+
+    /// The records cached for `name`, `qtype` and `qclass`, with their TTLs
+    /// lowered by the time they have spent in the cache. `None` if there
+    /// are none, including when the cache knows there can't be any.
+    pub fn get(&mut self, name: &str, qtype: QType, qclass: QClass) -> Option<Vec<Record>> {
+        let now = Instant::now();
+        self.get_at(name, qtype, qclass, now)
+    }
+
+    fn get_at(
+        &mut self,
+        name: &str,
+        qtype: QType,
+        qclass: QClass,
+        now: Instant,
+    ) -> Option<Vec<Record>> {
+        match self.lookup_at(name, qtype, qclass, now)? {
+            Cached::Records(records) => Some(records),
+            Cached::NoData { .. } | Cached::NxDomain { .. } => None,
+        }
+    }
+
+    /// Everything the cache knows about `name`, `qtype` and `qclass`:
+    /// records, or that there are none
+    pub fn lookup(&mut self, name: &str, qtype: QType, qclass: QClass) -> Option<Cached> {
+        let now = Instant::now();
+        self.lookup_at(name, qtype, qclass, now)
+    }
+
+    fn lookup_at(
+        &mut self,
+        name: &str,
+        qtype: QType,
+        qclass: QClass,
+        now: Instant,
+    ) -> Option<Cached> {
+        let name = normalize(name);
+        let entry = match self.entry_at(&(name.clone(), None, qclass), now) {
+            Some(entry) => Some(entry),
+            None => self.entry_at(&(name, Some(qtype), qclass), now),
+        };
+        let Some(entry) = entry else {
+            self.misses += 1;
+            return None;
+        };
+        let elapsed = now.duration_since(entry.stored).as_secs() as u32;
+        let mut cached = entry.cached.clone();
+        self.hits += 1;
+
+        match &mut cached {
+            Cached::Records(records) => {
+                for record in records {
+                    record.set_ttl(record.ttl().min(MAX_TTL).saturating_sub(elapsed));
+                }
+            }
+            Cached::NoData { soa } | Cached::NxDomain { soa } => {
+                soa.set_ttl(soa.ttl().saturating_sub(elapsed));
+            }
+        }
+        Some(cached)
+    }
+
+    /// A response to `request` made up from the cache alone, if it has
+    /// what's needed: the records asked for, or the SOA which says there
+    /// aren't any, with `NameError` if the name doesn't exist at all.
+    pub fn respond(&mut self, request: &Packet) -> Option<Packet> {
+        let [question] = request.questions.as_slice() else {
+            return None;
+        };
+
+        let mut response = response_to(request);
+        response.header.recursion_available = true;
+        match self.lookup(&question.name, question.qtype, question.qclass)? {
+            Cached::Records(records) => response.answers = records,
+            Cached::NoData { soa } => response.authorities.push(soa),
+            Cached::NxDomain { soa } => {
+                response.header.rcode = RCode::NameError;
+                response.authorities.push(soa);
+            }
+        }
+        Some(response)
+    }
+
+    /// The live entry for `key`, marked as just used. An expired one is
+    /// removed instead.
+    fn entry_at(&mut self, key: &CacheKey, now: Instant) -> Option<&Entry> {
+        let expired = now >= self.entries.get(key)?.expires;
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.lru.remove(&entry.used);
+        self.lru.insert(self.tick, key.clone());
+        entry.used = self.tick;
+        Some(entry)
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.used);
+        }
+    }
+
+    /// Drop every entry that has expired
+    pub fn purge_expired(&mut self) {
+        self.purge_expired_at(Instant::now());
+    }
+
+    fn purge_expired_at(&mut self, now: Instant) {
+        let lru = &mut self.lru;
+        self.entries.retain(|_, entry| {
+            let live = now < entry.expires;
+            if !live {
+                lru.remove(&entry.used);
+            }
+            live
+        });
+    }
+
+    /// How many record sets are cached, including any that have expired but
+    /// haven't been purged yet
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many lookups found a live entry
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How many lookups found nothing, or only an expired entry
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use std::net::Ipv4Addr;
+
+    fn a(domain: &str, last: u8, ttl: u32) -> Record {
+        Record::A {
+            domain: domain.to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, last),
+            class: QClass::IN,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn test_ttl_counts_down() {
+        let mut cache = Cache::new(10);
+        let start = Instant::now();
+        cache.insert_at(
+            &[a("www.example.com", 1, 300), a("www.example.com", 2, 60)],
+            start,
+        );
+
+        let records = cache
+            .get_at(
+                "WWW.example.com.",
+                QType::A,
+                QClass::IN,
+                start + Duration::from_secs(20),
+            )
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![a("www.example.com", 1, 280), a("www.example.com", 2, 40)]
+        );
+
+        // The set expires with its shortest TTL
+        let later = start + Duration::from_secs(60);
+        assert_eq!(
+            cache.get_at("www.example.com", QType::A, QClass::IN, later),
+            None
+        );
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut cache = Cache::new(10);
+        cache.insert(&[a("www.example.com", 1, 300)]);
+
+        assert!(cache
+            .get("www.example.com", QType::AAAA, QClass::IN)
+            .is_none());
+        assert!(cache.get("www.example.com", QType::A, QClass::CH).is_none());
+        assert!(cache.get("example.com", QType::A, QClass::IN).is_none());
+        assert!(cache.get("www.example.com", QType::A, QClass::IN).is_some());
+        assert_eq!(cache.misses(), 3);
+    }
+
+    #[test]
+    fn test_zero_ttl_and_cap() {
+        let mut cache = Cache::new(10);
+        let start = Instant::now();
+        cache.insert_at(
+            &[
+                a("zero.example.com", 1, 0),
+                a("long.example.com", 1, u32::MAX),
+            ],
+            start,
+        );
+        assert_eq!(cache.len(), 1);
+
+        let records = cache
+            .get_at("long.example.com", QType::A, QClass::IN, start)
+            .unwrap();
+        assert_eq!(records[0].ttl(), MAX_TTL);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = Cache::new(2);
+        let start = Instant::now();
+        cache.insert_at(&[a("one.example.com", 1, 300)], start);
+        cache.insert_at(&[a("two.example.com", 2, 300)], start);
+
+        // Using one makes two the least recently used
+        assert!(cache
+            .get_at("one.example.com", QType::A, QClass::IN, start)
+            .is_some());
+        cache.insert_at(&[a("three.example.com", 3, 300)], start);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .get_at("two.example.com", QType::A, QClass::IN, start)
+            .is_none());
+        assert!(cache
+            .get_at("one.example.com", QType::A, QClass::IN, start)
+            .is_some());
+        assert!(cache
+            .get_at("three.example.com", QType::A, QClass::IN, start)
+            .is_some());
+    }
+
+    #[test]
+    fn test_expired_entries_go_first() {
+        let mut cache = Cache::new(2);
+        let start = Instant::now();
+        cache.insert_at(&[a("short.example.com", 1, 10)], start);
+        cache.insert_at(&[a("long.example.com", 2, 300)], start);
+        assert!(cache
+            .get_at("short.example.com", QType::A, QClass::IN, start)
+            .is_some());
+
+        // short is more recently used, but has expired by now
+        let later = start + Duration::from_secs(30);
+        cache.insert_at(&[a("new.example.com", 3, 300)], later);
+        assert!(cache
+            .get_at("long.example.com", QType::A, QClass::IN, later)
+            .is_some());
+        assert!(cache
+            .get_at("new.example.com", QType::A, QClass::IN, later)
+            .is_some());
+    }
+
+    fn soa(ttl: u32, minimum: u32) -> Record {
+        Record::SOA {
+            domain: "example.com".to_string(),
+            mname: "ns1.example.com".to_string(),
+            rname: "hostmaster.example.com".to_string(),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum,
+            class: QClass::IN,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn test_nxdomain() {
+        let mut cache = Cache::new(10);
+        let start = Instant::now();
+        cache.insert_negative(
+            ("nope.example.com".to_string(), None, QClass::IN),
+            &soa(3600, 300),
+            start,
+        );
+
+        // Every type is covered, and the SOA's TTL is the time left
+        let later = start + Duration::from_secs(100);
+        for qtype in [QType::A, QType::MX] {
+            assert_eq!(
+                cache.lookup_at("nope.example.com", qtype, QClass::IN, later),
+                Some(Cached::NxDomain { soa: soa(200, 300) })
+            );
+        }
+        assert_eq!(
+            cache.get_at("nope.example.com", QType::A, QClass::IN, later),
+            None
+        );
+
+        let expired = start + Duration::from_secs(300);
+        assert_eq!(
+            cache.lookup_at("nope.example.com", QType::A, QClass::IN, expired),
+            None
+        );
+
+        // Records showing up for the name replace what we knew
+        cache.insert_nxdomain("nope.example.com", QClass::IN, &soa(60, 300));
+        cache.insert(&[a("nope.example.com", 1, 300)]);
+        assert!(cache
+            .get("nope.example.com", QType::A, QClass::IN)
+            .is_some());
+    }
+
+    #[test]
+    fn test_nodata() {
+        let mut cache = Cache::new(10);
+        let start = Instant::now();
+        cache.insert_negative(
+            ("www.example.com".to_string(), Some(QType::AAAA), QClass::IN),
+            &soa(60, 300),
+            start,
+        );
+        cache.insert_at(&[a("www.example.com", 1, 300)], start);
+
+        assert_eq!(
+            cache.lookup_at("www.example.com", QType::AAAA, QClass::IN, start),
+            Some(Cached::NoData { soa: soa(60, 300) })
+        );
+        assert!(cache
+            .get_at("www.example.com", QType::A, QClass::IN, start)
+            .is_some());
+        assert_eq!(
+            cache.lookup_at("www.example.com", QType::MX, QClass::IN, start),
+            None
+        );
+
+        // An SOA with a TTL of 0 means the answer mustn't be cached
+        cache.insert_nodata("www.example.com", QType::MX, QClass::IN, &soa(0, 300));
+        assert_eq!(cache.lookup("www.example.com", QType::MX, QClass::IN), None);
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut cache = Cache::new(10);
+        cache.insert_nxdomain("nope.example.com", QClass::IN, &soa(3600, 300));
+        cache.insert_nodata("www.example.com", QType::AAAA, QClass::IN, &soa(3600, 300));
+        cache.insert(&[a("www.example.com", 1, 300)]);
+
+        let request = Client::build_query("nope.example.com", QType::A);
+        let response = cache.respond(&request).unwrap();
+        assert_eq!(response.header.id, request.header.id);
+        assert_eq!(response.header.rcode, RCode::NameError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities, vec![soa(300, 300)]);
+
+        let request = Client::build_query("www.example.com", QType::AAAA);
+        let response = cache.respond(&request).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities, vec![soa(300, 300)]);
+
+        let request = Client::build_query("www.example.com", QType::A);
+        let response = cache.respond(&request).unwrap();
+        assert_eq!(response.answers, vec![a("www.example.com", 1, 300)]);
+        assert!(response.authorities.is_empty());
+
+        let request = Client::build_query("www.example.com", QType::MX);
+        assert!(cache.respond(&request).is_none());
+    }
+}
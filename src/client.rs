@@ -0,0 +1,326 @@
+use crate::byte_packet_buffer::{BytePacketBuffer, DEFAULT_SIZE};
+use crate::error::ClientError;
+use crate::header::Header;
+use crate::packet::Packet;
+use crate::question::{QClass, QType, Question};
+use crate::random::random_u64;
+use crate::tcp::TcpConnection;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// A stub resolver client which sends queries to a single nameserver over
+/// UDP, falling back to TCP for responses too large for UDP.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub server: SocketAddr,
+    /// How long to wait for a response to each attempt
+    pub timeout: Duration,
+    /// How many times to resend the query after the first attempt timed out
+    pub retries: usize,
+}
+
+impl Client {
+    pub fn new(server: SocketAddr) -> Client {
+        Client {
+            server,
+            timeout: Duration::from_secs(2),
+            retries: 2,
+        }
+    }
+
+    /// Ask the server for records of `qtype` for `name`, with recursion
+    /// desired and a random id.
+    pub fn lookup(&self, name: &str, qtype: QType) -> Result<Packet, ClientError> {
+        self.query(&Self::build_query(name, qtype))
+    }
+
+    /// A query packet for a single question with a random id
+    pub fn build_query(name: &str, qtype: QType) -> Packet {
+        Packet {
+            header: Header {
+                id: random_u64() as u16,
+                is_reply: false,
+                recursion_desired: true,
+                ..Header::default()
+            },
+            questions: vec![Question {
+                name: name.to_string(),
+                qtype,
+                qclass: QClass::IN,
+            }],
+            ..Packet::default()
+        }
+    }
+
+    /// Send `request` over UDP and wait for the response that matches its
+    /// id and question. Anything else arriving on the socket, e.g. a late
+    /// answer to an earlier attempt or a spoofed reply, is ignored. If the
+    /// response is truncated the query is repeated over TCP.
+    pub fn query(&self, request: &Packet) -> Result<Packet, ClientError> {
+        let response = self.query_udp(request)?;
+        if response.header.truncation {
+            return self.query_tcp(request);
+        }
+
+        Ok(response)
+    }
+
+    /// Send `request` over a new TCP connection and wait for its response
+    pub fn query_tcp(&self, request: &Packet) -> Result<Packet, ClientError> {
+        let mut connection = TcpConnection::connect(self.server, self.timeout)?;
+        connection.query(request)
+    }
+
+    /// Send `request` over UDP only, returning truncated responses as they
+    /// are.
+    pub fn query_udp(&self, request: &Packet) -> Result<Packet, ClientError> {
+        let mut request = request.clone();
+        let mut req_buffer = BytePacketBuffer::new();
+        request.write(&mut req_buffer)?;
+
+        let bind_addr: SocketAddr = match self.server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind_addr)?;
+        // Connecting makes the kernel drop datagrams from any other address.
+        socket.connect(self.server)?;
+
+        // We can accept responses as large as we told the server we could.
+        let size = request
+            .edns
+            .as_ref()
+            .map_or(DEFAULT_SIZE, |opt| opt.effective_udp_payload_size());
+
+        let attempts = self.retries + 1;
+        for _ in 0..attempts {
+            socket.send(req_buffer.filled())?;
+
+            if let Some(response) = self.receive(&socket, &request, size)? {
+                return Ok(response);
+            }
+        }
+
+        Err(ClientError::Timeout { attempts })
+    }
+
+    /// Wait up to the timeout for a matching response, `None` if none came.
+    fn receive(
+        &self,
+        socket: &UdpSocket,
+        request: &Packet,
+        size: usize,
+    ) -> Result<Option<Packet>, ClientError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut res_buffer = BytePacketBuffer::with_capacity(size);
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+
+            let len = match socket.recv(&mut res_buffer.buf) {
+                Ok(len) => len,
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let mut buffer = BytePacketBuffer::from_bytes(&res_buffer.buf[..len]);
+            match Packet::from_buffer(&mut buffer) {
+                Ok(response) if Self::matches(request, &response) => return Ok(Some(response)),
+                _ => continue,
+            }
+        }
+    }
+
+    /// Whether `response` answers `request`: same id and same questions
+    pub fn matches(request: &Packet, response: &Packet) -> bool {
+        response.header.is_reply
+            && response.header.id == request.header.id
+            && response.questions.len() == request.questions.len()
+            && response
+                .questions
+                .iter()
+                .zip(&request.questions)
+                .all(|(res, req)| {
+                    res.qtype == req.qtype
+                        && res.qclass == req.qclass
+                        && res
+                            .name
+                            .eq_ignore_ascii_case(req.name.trim_end_matches('.'))
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::byte_packet_buffer::MAX_SIZE;
+    use crate::record::Record;
+    use crate::tcp::{read_framed, write_framed};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A stand-in server which reads one query per entry in `replies` and
+    /// lets the closure turn it into the datagrams to send back.
+    fn stand_in_server<F>(replies: usize, respond: F) -> SocketAddr
+    where
+        F: Fn(usize, &Packet) -> Vec<Packet> + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            for i in 0..replies {
+                let mut req_buffer = BytePacketBuffer::new();
+                let (len, src) = socket.recv_from(&mut req_buffer.buf).unwrap();
+                let mut req_buffer = BytePacketBuffer::from_bytes(&req_buffer.buf[..len]);
+                let request = Packet::from_buffer(&mut req_buffer).unwrap();
+
+                for mut response in respond(i, &request) {
+                    let mut res_buffer = BytePacketBuffer::new();
+                    response.write(&mut res_buffer).unwrap();
+                    socket.send_to(res_buffer.filled(), src).unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    fn answer(request: &Packet) -> Packet {
+        let mut response = request.clone();
+        response.header.is_reply = true;
+        response.answers.push(Record::A {
+            domain: request.questions[0].name.clone(),
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+            class: QClass::IN,
+            ttl: 60,
+        });
+        response
+    }
+
+    fn client(server: SocketAddr) -> Client {
+        Client {
+            timeout: Duration::from_millis(200),
+            ..Client::new(server)
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let server = stand_in_server(1, |_, request| vec![answer(request)]);
+
+        let response = client(server).lookup("codecrafters.io", QType::A).unwrap();
+        assert!(response.header.is_reply);
+        assert_eq!(response.questions[0].name, "codecrafters.io");
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn test_ignores_mismatched_responses() {
+        let server = stand_in_server(1, |_, request| {
+            let mut wrong_id = answer(request);
+            wrong_id.header.id = request.header.id.wrapping_add(1);
+
+            let mut wrong_question = answer(request);
+            wrong_question.questions[0].name = "example.com".to_string();
+
+            let mut not_a_reply = answer(request);
+            not_a_reply.header.is_reply = false;
+
+            let mut right = answer(request);
+            right.answers[0] = Record::A {
+                domain: request.questions[0].name.clone(),
+                addr: Ipv4Addr::new(192, 0, 2, 42),
+                class: QClass::IN,
+                ttl: 60,
+            };
+
+            vec![wrong_id, wrong_question, not_a_reply, right]
+        });
+
+        let response = client(server).lookup("codecrafters.io", QType::A).unwrap();
+        match &response.answers[0] {
+            Record::A { addr, .. } => assert_eq!(addr, &Ipv4Addr::new(192, 0, 2, 42)),
+            _ => panic!("Wrong record type"),
+        }
+    }
+
+    #[test]
+    fn test_retries_after_timeout() {
+        // The first query goes unanswered.
+        let server = stand_in_server(2, |i, request| {
+            if i == 0 {
+                vec![]
+            } else {
+                vec![answer(request)]
+            }
+        });
+
+        let response = client(server).lookup("codecrafters.io", QType::A).unwrap();
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn test_falls_back_to_tcp_when_truncated() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = udp.local_addr().unwrap();
+        let tcp = TcpListener::bind(server).unwrap();
+
+        thread::spawn(move || {
+            let mut req_buffer = BytePacketBuffer::new();
+            let (len, src) = udp.recv_from(&mut req_buffer.buf).unwrap();
+            let mut req_buffer = BytePacketBuffer::from_bytes(&req_buffer.buf[..len]);
+            let request = Packet::from_buffer(&mut req_buffer).unwrap();
+
+            let mut truncated = request.clone();
+            truncated.header.is_reply = true;
+            truncated.header.truncation = true;
+            let mut res_buffer = BytePacketBuffer::new();
+            truncated.write(&mut res_buffer).unwrap();
+            udp.send_to(res_buffer.filled(), src).unwrap();
+
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut req_buffer = read_framed(&mut stream).unwrap();
+            let request = Packet::from_buffer(&mut req_buffer).unwrap();
+            let mut response = answer(&request);
+            for i in 0..50 {
+                response.answers.push(Record::A {
+                    domain: request.questions[0].name.clone(),
+                    addr: Ipv4Addr::new(10, 0, 0, i),
+                    class: QClass::IN,
+                    ttl: 60,
+                });
+            }
+            let mut res_buffer = BytePacketBuffer::with_max_size(MAX_SIZE);
+            response.write(&mut res_buffer).unwrap();
+            assert!(res_buffer.pos() > 512);
+            write_framed(&mut stream, res_buffer.filled()).unwrap();
+        });
+
+        let response = client(server).lookup("codecrafters.io", QType::A).unwrap();
+        assert!(!response.header.truncation);
+        assert_eq!(response.answers.len(), 51);
+    }
+
+    #[test]
+    fn test_times_out() {
+        let server = stand_in_server(3, |_, _| vec![]);
+
+        let client = Client {
+            retries: 1,
+            ..client(server)
+        };
+        match client.lookup("codecrafters.io", QType::A) {
+            Err(ClientError::Timeout { attempts }) => assert_eq!(attempts, 2),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+}
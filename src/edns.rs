@@ -0,0 +1,197 @@
+// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.2
+//     +------------+--------------+------------------------------+
+//     | Field Name | Field Type   | Description                  |
+//     +------------+--------------+------------------------------+
+//     | NAME       | domain name  | MUST be 0 (root domain)      |
+//     | TYPE       | u_int16_t    | OPT (41)                     |
+//     | CLASS      | u_int16_t    | requestor's UDP payload size |
+//     | TTL        | u_int32_t    | extended RCODE and flags     |
+//     | RDLEN      | u_int16_t    | length of all RDATA          |
+//     | RDATA      | octet stream | {attribute,value} pairs      |
+//     +------------+--------------+------------------------------+
+//
+//                  +0 (MSB)                            +1 (LSB)
+//       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+//    0: |         EXTENDED-RCODE        |            VERSION            |
+//       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+//    2: | DO|                           Z                               |
+//       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::{ParseError, WriteError};
+use crate::question::QType;
+
+/// The EDNS(0) OPT pseudo-record, which lives in the additional section
+/// and extends the header with a larger UDP payload size, more RCODE bits
+/// and flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opt {
+    /// The largest UDP message the sender can reassemble. Values below 512
+    /// are treated as 512.
+    pub udp_payload_size: u16,
+    /// The upper 8 bits of the 12 bit response code, the lower 4 of which
+    /// are the header's RCODE.
+    pub extended_rcode: u8,
+    /// The EDNS version the sender implements, 0 for RFC 6891.
+    pub version: u8,
+    /// DNSSEC OK - the sender can handle DNSSEC records in the response
+    /// (RFC 3225).
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+/// A single {attribute, value} pair from the OPT RDATA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+/// The payload size recommended by DNS Flag Day 2020, small enough to avoid
+/// IP fragmentation on practically every path.
+pub const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+impl Default for Opt {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl Opt {
+    /// Read an OPT record, starting at its owner name
+    pub fn read(buffer: &mut BytePacketBuffer) -> Result<Opt, ParseError> {
+        let mut domain = String::new();
+        buffer.read_qname(&mut domain)?;
+
+        let qtype = buffer.read_u16()?;
+        let udp_payload_size = buffer.read_u16()?;
+        let flags = buffer.read_u32()?;
+        let data_len = buffer.read_u16()?;
+
+        let end = buffer.pos() + data_len as usize;
+        let mut options = Vec::new();
+        while buffer.pos() < end {
+            let code = buffer.read_u16()?;
+            let len = buffer.read_u16()? as usize;
+            let pos = buffer.pos();
+            if pos + len > end {
+                return Err(ParseError::InvalidRdata {
+                    offset: pos - 4,
+                    qtype,
+                    reason: "option overruns the rdata",
+                });
+            }
+            let data = buffer.get_range(pos, len)?.to_vec();
+            buffer.step(len)?;
+
+            options.push(EdnsOption { code, data });
+        }
+
+        Ok(Opt {
+            udp_payload_size,
+            extended_rcode: (flags >> 24) as u8,
+            version: ((flags >> 16) & 0xFF) as u8,
+            dnssec_ok: (flags & (1 << 15)) > 0,
+            options,
+        })
+    }
+
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<usize, WriteError> {
+        let start_pos = buffer.pos();
+
+        buffer.write_qname("")?;
+        buffer.write_u16(QType::OPT.to_num())?;
+        buffer.write_u16(self.udp_payload_size)?;
+        buffer.write_u8(self.extended_rcode)?;
+        buffer.write_u8(self.version)?;
+        buffer.write_u16((self.dnssec_ok as u16) << 15)?;
+
+        let pos = buffer.pos();
+        buffer.write_u16(0)?;
+
+        for option in &self.options {
+            buffer.write_u16(option.code)?;
+            buffer.write_u16(option.data.len() as u16)?;
+            for b in &option.data {
+                buffer.write_u8(*b)?;
+            }
+        }
+
+        let size = buffer.pos() - (pos + 2);
+        buffer.set_u16(pos, size as u16)?;
+
+        Ok(buffer.pos() - start_pos)
+    }
+
+    /// The payload size to actually use: RFC 6891 §6.2.5 says anything
+    /// below 512 means 512.
+    pub fn effective_udp_payload_size(&self) -> usize {
+        (self.udp_payload_size as usize).max(512)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_round_trip() {
+        let opt = Opt {
+            udp_payload_size: 4096,
+            extended_rcode: 1,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![EdnsOption {
+                code: 10,
+                data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            }],
+        };
+
+        let mut buffer = BytePacketBuffer::new();
+        let size = opt.write(&mut buffer).unwrap();
+        assert_eq!(size, 11 + 12);
+        assert_eq!(
+            buffer.filled(),
+            [
+                0, // root
+                0, 41, // OPT
+                16, 0, // udp payload size
+                1, 0, 128, 0, // extended rcode, version, DO
+                0, 12, // rdlength
+                0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8, // cookie option
+            ]
+        );
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(Opt::read(&mut buffer).unwrap(), opt);
+    }
+
+    #[test]
+    fn test_option_overrunning_rdata() {
+        let bytes = [0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 5, 0, 10, 0, 8, 1];
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        assert_eq!(
+            Opt::read(&mut buffer),
+            Err(ParseError::InvalidRdata {
+                offset: 11,
+                qtype: 41,
+                reason: "option overruns the rdata",
+            })
+        );
+    }
+
+    #[test]
+    fn test_effective_udp_payload_size() {
+        let opt = Opt {
+            udp_payload_size: 100,
+            ..Opt::default()
+        };
+        assert_eq!(opt.effective_udp_payload_size(), 512);
+        assert_eq!(Opt::default().effective_udp_payload_size(), 1232);
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why a message could not be parsed. Every variant carries the offset into
+/// the message at which the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The message ended before the byte at `offset` could be read.
+    UnexpectedEnd { offset: usize },
+    /// Following the compression pointers of the name at `offset` took more
+    /// than `limit` jumps, which usually means the pointers form a loop.
+    TooManyJumps { offset: usize, limit: usize },
+    /// The length byte at `offset` uses one of the reserved label types
+    /// (0b01 or 0b10 in its two most significant bits).
+    InvalidLabel { offset: usize, byte: u8 },
+    /// The name starting at `offset` is longer than 255 bytes.
+    NameTooLong { offset: usize },
+    /// The RDATA at `offset` doesn't have the shape its type requires.
+    InvalidRdata {
+        offset: usize,
+        qtype: u16,
+        reason: &'static str,
+    },
+    /// A second OPT record was found at `offset`; RFC 6891 allows only one.
+    DuplicateOpt { offset: usize },
+}
+
+impl ParseError {
+    /// The offset into the message at which the problem was found
+    pub fn offset(&self) -> usize {
+        match *self {
+            ParseError::UnexpectedEnd { offset }
+            | ParseError::TooManyJumps { offset, .. }
+            | ParseError::InvalidLabel { offset, .. }
+            | ParseError::NameTooLong { offset }
+            | ParseError::InvalidRdata { offset, .. }
+            | ParseError::DuplicateOpt { offset } => offset,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd { offset } => {
+                write!(f, "unexpected end of message at offset {}", offset)
+            }
+            ParseError::TooManyJumps { offset, limit } => write!(
+                f,
+                "limit of {} jumps exceeded reading name at offset {}",
+                limit, offset
+            ),
+            ParseError::InvalidLabel { offset, byte } => {
+                write!(f, "invalid label type {:#04x} at offset {}", byte, offset)
+            }
+            ParseError::NameTooLong { offset } => {
+                write!(f, "name at offset {} exceeds 255 bytes", offset)
+            }
+            ParseError::InvalidRdata {
+                offset,
+                qtype,
+                reason,
+            } => write!(
+                f,
+                "invalid rdata for type {} at offset {}: {}",
+                qtype, offset, reason
+            ),
+            ParseError::DuplicateOpt { offset } => {
+                write!(f, "second OPT record at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Why a message could not be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// Writing at `offset` would grow the message past the buffer's
+    /// `max_size`.
+    BufferFull { offset: usize, max_size: usize },
+    /// A label of the name is longer than 63 bytes.
+    LabelTooLong { label: String },
+    /// The name is longer than 255 bytes once encoded.
+    NameTooLong { name: String },
+    /// A TXT character-string is longer than 255 bytes.
+    CharacterStringTooLong { len: usize },
+    /// A TXT record has no character-strings, where RFC 1035 §3.3.14
+    /// requires at least one.
+    EmptyTxt,
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::BufferFull { offset, max_size } => write!(
+                f,
+                "cannot write at offset {}, message is limited to {} bytes",
+                offset, max_size
+            ),
+            WriteError::LabelTooLong { label } => {
+                write!(f, "label {:?} exceeds 63 characters of length", label)
+            }
+            WriteError::NameTooLong { name } => {
+                write!(f, "name {:?} exceeds 255 bytes", name)
+            }
+            WriteError::CharacterStringTooLong { len } => {
+                write!(f, "character-string of {} bytes exceeds 255 bytes", len)
+            }
+            WriteError::EmptyTxt => write!(f, "TXT record has no character-strings"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Why a query sent by the `Client` didn't produce a response.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The socket failed.
+    Io(std::io::Error),
+    /// The query couldn't be serialized.
+    Write(WriteError),
+    /// A response read from a TCP stream couldn't be parsed. Over UDP such
+    /// responses are ignored instead, as they may well be spoofed.
+    Parse(ParseError),
+    /// No matching response arrived within the timeout, after `attempts`
+    /// sends of the query.
+    Timeout { attempts: usize },
+    /// Two of the queries pipelined on one connection share the id `id`, so
+    /// their responses couldn't be told apart.
+    DuplicateId { id: u16 },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(err) => write!(f, "socket error: {}", err),
+            ClientError::Write(err) => write!(f, "could not write query: {}", err),
+            ClientError::Parse(err) => write!(f, "could not parse response: {}", err),
+            ClientError::Timeout { attempts } => {
+                write!(f, "no response after {} attempts", attempts)
+            }
+            ClientError::DuplicateId { id } => {
+                write!(f, "more than one query with id {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(err) => Some(err),
+            ClientError::Write(err) => Some(err),
+            ClientError::Parse(err) => Some(err),
+            ClientError::Timeout { .. } | ClientError::DuplicateId { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ClientError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            // A read timeout on a socket surfaces as one of these, depending
+            // on the platform.
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                ClientError::Timeout { attempts: 1 }
+            }
+            _ => ClientError::Io(err),
+        }
+    }
+}
+
+impl From<ParseError> for ClientError {
+    fn from(err: ParseError) -> Self {
+        ClientError::Parse(err)
+    }
+}
+
+impl From<WriteError> for ClientError {
+    fn from(err: WriteError) -> Self {
+        ClientError::Write(err)
+    }
+}
+
+/// Why a zone file could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneFileError {
+    /// The file the problem is in, `None` for text that wasn't read from a
+    /// file
+    pub file: Option<PathBuf>,
+    /// The line the problem is on, counting from 1. For an entry continued
+    /// over several lines with parentheses, the line it starts on. 0 if the
+    /// file couldn't be read at all.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ZoneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), 0) => write!(f, "{}: {}", file.display(), self.message),
+            (Some(file), line) => write!(f, "{}:{}: {}", file.display(), line, self.message),
+            (None, line) => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ZoneFileError {}
+
+/// Why the `Resolver` couldn't resolve a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// None of the nameservers for `zone` gave a usable response: they
+    /// timed out, failed, or referred us somewhere that isn't closer to the
+    /// name. The root is the empty string.
+    NoUsableServers { zone: String },
+    /// Resolving would take more than `limit` queries.
+    TooManyQueries { limit: usize },
+    /// The name is an alias for an alias, and so on, more than `limit`
+    /// times; usually a loop.
+    CnameChainTooLong { limit: usize },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::NoUsableServers { zone } if zone.is_empty() => {
+                write!(f, "no usable root servers")
+            }
+            ResolveError::NoUsableServers { zone } => {
+                write!(f, "no usable nameservers for {}", zone)
+            }
+            ResolveError::TooManyQueries { limit } => {
+                write!(f, "gave up after {} queries", limit)
+            }
+            ResolveError::CnameChainTooLong { limit } => {
+                write!(f, "more than {} CNAMEs in a chain", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
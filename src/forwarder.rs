@@ -0,0 +1,375 @@
+use crate::client::Client;
+use crate::header::RCode;
+use crate::packet::Packet;
+use crate::random::random_u64;
+use crate::server::{response_to, RequestHandler};
+use std::net::SocketAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Which upstream a `Forwarder` asks first. Whichever it is, the others are
+/// tried in turn when it times out or fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// The first upstream in the list that is healthy
+    #[default]
+    FirstHealthy,
+    /// Each upstream in turn, spreading the load
+    RoundRobin,
+    /// The healthy upstream with the lowest smoothed round trip time.
+    /// Upstreams that haven't been measured yet get tried first.
+    Fastest,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Upstream {
+    /// Smoothed round trip time, `None` until the first response
+    rtt: Option<Duration>,
+    /// When a failed upstream may be asked first again
+    down_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Pool {
+    upstreams: Vec<Upstream>,
+    /// Where the next round robin starts
+    next: usize,
+}
+
+/// A handler which answers queries by passing them on to upstream
+/// resolvers.
+#[derive(Debug)]
+pub struct Forwarder {
+    pub upstreams: Vec<SocketAddr>,
+    pub strategy: Strategy,
+    /// How long to wait for each upstream
+    pub timeout: Duration,
+    /// How long an upstream that timed out or couldn't be reached goes to
+    /// the back of the queue. One that answers SERVFAIL doesn't: that is
+    /// usually down to the zone asked about rather than the upstream.
+    pub hold_down: Duration,
+    pool: Mutex<Pool>,
+}
+
+impl Forwarder {
+    pub fn new(upstreams: Vec<SocketAddr>, strategy: Strategy) -> Forwarder {
+        Forwarder {
+            pool: Mutex::new(Pool {
+                upstreams: vec![Upstream::default(); upstreams.len()],
+                next: 0,
+            }),
+            upstreams,
+            strategy,
+            timeout: Duration::from_secs(2),
+            hold_down: Duration::from_secs(30),
+        }
+    }
+
+    /// The indexes of the upstreams in the order to try them: by strategy,
+    /// with those held down after failing last.
+    fn order(&self) -> Vec<usize> {
+        let mut pool = self.pool.lock().unwrap_or_else(PoisonError::into_inner);
+        // `upstreams` is public, so the pool may need to catch up with it.
+        pool.upstreams
+            .resize(self.upstreams.len(), Upstream::default());
+
+        let mut order: Vec<usize> = (0..self.upstreams.len()).collect();
+        match self.strategy {
+            Strategy::FirstHealthy => {}
+            Strategy::RoundRobin => {
+                if !order.is_empty() {
+                    order.rotate_left(pool.next % self.upstreams.len());
+                }
+                pool.next = pool.next.wrapping_add(1);
+            }
+            Strategy::Fastest => {
+                order.sort_by_key(|&i| pool.upstreams[i].rtt.unwrap_or_default());
+            }
+        }
+
+        let now = Instant::now();
+        let down = |i: &usize| {
+            pool.upstreams[*i]
+                .down_until
+                .is_some_and(|until| until > now)
+        };
+        order.sort_by_key(down);
+        order
+    }
+
+    fn responded(&self, i: usize, rtt: Duration) {
+        let mut pool = self.pool.lock().unwrap_or_else(PoisonError::into_inner);
+        let upstream = &mut pool.upstreams[i];
+        upstream.down_until = None;
+        // The same smoothing as TCP's SRTT (RFC 6298 §2).
+        upstream.rtt = Some(match upstream.rtt {
+            Some(srtt) => srtt * 7 / 8 + rtt / 8,
+            None => rtt,
+        });
+    }
+
+    fn failed(&self, i: usize) {
+        let mut pool = self.pool.lock().unwrap_or_else(PoisonError::into_inner);
+        pool.upstreams[i].down_until = Some(Instant::now() + self.hold_down);
+    }
+}
+
+impl RequestHandler for Forwarder {
+    fn handle(&self, request: &Packet) -> Packet {
+        // Upstream sees an id of our own, so that clients can't pick the ids
+        // our queries go out with.
+        let mut forwarded = request.clone();
+        forwarded.header.id = random_u64() as u16;
+
+        let mut failure = None;
+        for i in self.order() {
+            let client = Client {
+                timeout: self.timeout,
+                retries: 0,
+                ..Client::new(self.upstreams[i])
+            };
+
+            let start = Instant::now();
+            match client.query(&forwarded) {
+                // Maybe another upstream can resolve this name, but this
+                // one is still fine for others.
+                Ok(response) if response.header.rcode == RCode::ServerFailure => {
+                    self.responded(i, start.elapsed());
+                    failure = Some(response);
+                }
+                Ok(mut response) => {
+                    self.responded(i, start.elapsed());
+                    response.header.id = request.header.id;
+                    return response;
+                }
+                Err(_) => self.failed(i),
+            }
+        }
+
+        let mut response = failure.unwrap_or_else(|| {
+            let mut response = response_to(request);
+            response.header.rcode = RCode::ServerFailure;
+            response
+        });
+        response.header.id = request.header.id;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::question::{QClass, QType};
+    use crate::record::Record;
+    use crate::server;
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// An upstream that answers with its own number in an A record, after
+    /// `delay`, and keeps the ids it was sent
+    struct Numbered {
+        number: u8,
+        delay: Duration,
+        rcode: RCode,
+        ids: Mutex<Vec<u16>>,
+    }
+
+    impl RequestHandler for Numbered {
+        fn handle(&self, request: &Packet) -> Packet {
+            self.ids.lock().unwrap().push(request.header.id);
+            thread::sleep(self.delay);
+
+            let mut response = response_to(request);
+            response.header.rcode = self.rcode;
+            response.answers.push(Record::A {
+                domain: request.questions[0].name.clone(),
+                addr: Ipv4Addr::new(192, 0, 2, self.number),
+                class: QClass::IN,
+                ttl: 60,
+            });
+            response
+        }
+    }
+
+    fn upstream(number: u8, delay: Duration, rcode: RCode) -> (SocketAddr, Arc<Numbered>) {
+        let handler = Arc::new(Numbered {
+            number,
+            delay,
+            rcode,
+            ids: Mutex::new(Vec::new()),
+        });
+        let addr = server::spawn("127.0.0.1:0".parse().unwrap(), handler.clone()).unwrap();
+        (addr, handler)
+    }
+
+    /// An upstream that never answers
+    fn silent() -> (SocketAddr, UdpSocket) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        (socket.local_addr().unwrap(), socket)
+    }
+
+    fn forwarder(upstreams: Vec<SocketAddr>, strategy: Strategy) -> Forwarder {
+        Forwarder {
+            timeout: Duration::from_millis(100),
+            ..Forwarder::new(upstreams, strategy)
+        }
+    }
+
+    fn answered_by(response: &Packet) -> u8 {
+        match response.answers.first() {
+            Some(Record::A { addr, .. }) => addr.octets()[3],
+            other => panic!("Expected an A record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rewrites_id() {
+        let (addr, handler) = upstream(1, Duration::ZERO, RCode::NoError);
+        let forwarder = forwarder(vec![addr], Strategy::FirstHealthy);
+
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let response = forwarder.handle(&request);
+        assert_eq!(response.header.id, request.header.id);
+        assert_eq!(answered_by(&response), 1);
+        assert_eq!(handler.ids.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fails_over() {
+        let (dead, _socket) = silent();
+        let (broken, broken_handler) = upstream(1, Duration::ZERO, RCode::ServerFailure);
+        let (working, _) = upstream(2, Duration::ZERO, RCode::NoError);
+        let forwarder = forwarder(vec![dead, broken, working], Strategy::FirstHealthy);
+
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let start = Instant::now();
+        assert_eq!(answered_by(&forwarder.handle(&request)), 2);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // The upstream that timed out is held down, so the next query
+        // doesn't wait for it. The one that answered SERVFAIL is still
+        // asked first.
+        let start = Instant::now();
+        assert_eq!(answered_by(&forwarder.handle(&request)), 2);
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(broken_handler.ids.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_servfail_is_per_name() {
+        /// Fails names starting with "broken", and answers the rest
+        struct Picky;
+
+        impl RequestHandler for Picky {
+            fn handle(&self, request: &Packet) -> Packet {
+                let mut response = response_to(request);
+                if request.questions[0].name.starts_with("broken") {
+                    response.header.rcode = RCode::ServerFailure;
+                } else {
+                    response.answers.push(Record::A {
+                        domain: request.questions[0].name.clone(),
+                        addr: Ipv4Addr::new(192, 0, 2, 1),
+                        class: QClass::IN,
+                        ttl: 60,
+                    });
+                }
+                response
+            }
+        }
+
+        let picky = server::spawn("127.0.0.1:0".parse().unwrap(), Arc::new(Picky)).unwrap();
+        let (other, _) = upstream(2, Duration::ZERO, RCode::NoError);
+        let forwarder = forwarder(vec![picky, other], Strategy::FirstHealthy);
+
+        let request = Client::build_query("broken.codecrafters.io", QType::A);
+        assert_eq!(answered_by(&forwarder.handle(&request)), 2);
+
+        let request = Client::build_query("www.codecrafters.io", QType::A);
+        assert_eq!(answered_by(&forwarder.handle(&request)), 1);
+    }
+
+    #[test]
+    fn test_all_upstreams_fail() {
+        let (dead, _socket) = silent();
+        let (broken, _) = upstream(1, Duration::ZERO, RCode::ServerFailure);
+
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let response = forwarder(vec![dead], Strategy::FirstHealthy).handle(&request);
+        assert_eq!(response.header.rcode, RCode::ServerFailure);
+        assert_eq!(response.header.id, request.header.id);
+
+        // The upstream's own SERVFAIL is passed on
+        let response = forwarder(vec![broken, dead], Strategy::FirstHealthy).handle(&request);
+        assert_eq!(response.header.rcode, RCode::ServerFailure);
+        assert_eq!(response.header.id, request.header.id);
+        assert_eq!(answered_by(&response), 1);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let (first, first_handler) = upstream(1, Duration::ZERO, RCode::NoError);
+        let (second, second_handler) = upstream(2, Duration::ZERO, RCode::NoError);
+        let forwarder = forwarder(vec![first, second], Strategy::RoundRobin);
+
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let answers: Vec<u8> = (0..4)
+            .map(|_| answered_by(&forwarder.handle(&request)))
+            .collect();
+        assert_eq!(answers, vec![1, 2, 1, 2]);
+        assert_eq!(first_handler.ids.lock().unwrap().len(), 2);
+        assert_eq!(second_handler.ids.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_fastest() {
+        let (slow, _) = upstream(1, Duration::from_millis(30), RCode::NoError);
+        let (fast, _) = upstream(2, Duration::ZERO, RCode::NoError);
+        let forwarder = forwarder(vec![slow, fast], Strategy::Fastest);
+
+        // Both get measured first
+        let request = Client::build_query("codecrafters.io", QType::A);
+        assert_eq!(answered_by(&forwarder.handle(&request)), 1);
+        assert_eq!(answered_by(&forwarder.handle(&request)), 2);
+
+        for _ in 0..3 {
+            assert_eq!(answered_by(&forwarder.handle(&request)), 2);
+        }
+    }
+
+    #[test]
+    fn test_slow_queries_dont_block_others() {
+        /// Takes its time over names starting with "slow"
+        struct Slow;
+
+        impl RequestHandler for Slow {
+            fn handle(&self, request: &Packet) -> Packet {
+                if request.questions[0].name.starts_with("slow") {
+                    thread::sleep(Duration::from_millis(500));
+                }
+                response_to(request)
+            }
+        }
+
+        let upstream = server::spawn("127.0.0.1:0".parse().unwrap(), Arc::new(Slow)).unwrap();
+        let forwarder = Forwarder {
+            timeout: Duration::from_secs(2),
+            ..Forwarder::new(vec![upstream], Strategy::FirstHealthy)
+        };
+        let addr = server::spawn("127.0.0.1:0".parse().unwrap(), Arc::new(forwarder)).unwrap();
+
+        let slow = thread::spawn(move || {
+            let request = Client::build_query("slow.codecrafters.io", QType::A);
+            Client::new(addr).query_udp(&request).unwrap()
+        });
+        thread::sleep(Duration::from_millis(50));
+
+        let start = Instant::now();
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let response = Client::new(addr).query_udp(&request).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        assert_eq!(slow.join().unwrap().header.rcode, RCode::NoError);
+    }
+}
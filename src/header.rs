@@ -14,6 +14,8 @@
 //     |                    ARCOUNT                    |
 //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::{ParseError, WriteError};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Header {
@@ -46,8 +48,15 @@ pub struct Header {
     /// Recursion Available - this be is set or cleared in a response, and denotes whether recursive query support is available in the name server.
     /// bits = 24
     pub recursion_available: bool,
-    // Reserved (Z) 	3 bits 	Used by DNSSEC queries. At inception, it was reserved for future use.
-    // 25..=27
+    /// Reserved (Z) - the one bit of the original three that is still reserved for future use and must be zero.
+    /// bits = 25
+    pub z: bool,
+    /// Authentic Data - set in a response when the server has validated all of the data in the answer and authority sections with DNSSEC (RFC 4035 §3.2.3).
+    /// bits = 26
+    pub authentic_data: bool,
+    /// Checking Disabled - set in a query to ask the server not to perform DNSSEC validation, and copied into the response (RFC 4035 §3.2.2).
+    /// bits = 27
+    pub checking_disabled: bool,
     /// Response code - this 4 bit field is set as part of responses.
     /// bits = 25..=31
     pub rcode: RCode,
@@ -67,34 +76,50 @@ pub struct Header {
 
 /// A four bit field that specifies kind of query in this message.
 /// This value is set by the originator of a query and copied into the response.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Opcode {
-    /// a standard query
-    QUERY = 0,
-    /// an inverse query
-    IQUERY = 1,
-    /// a server status request
-    STATUS = 2,
-    //3-15 reserved for future use
+    /// a standard query (0)
+    QUERY,
+    /// an inverse query (1)
+    IQUERY,
+    /// a server status request (2)
+    STATUS,
+    /// a zone change notification, RFC 1996 (4)
+    NOTIFY,
+    /// a dynamic update, RFC 2136 (5)
+    UPDATE,
+    /// 3 and 6-15 are unassigned
+    Unknown(u8),
 }
 
 /// Response code - this 4 bit field is set as part of responses.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RCode {
-    /// No error condition
-    NoError = 0,
-    /// The name server was unable to interpret the query.
-    FormatError = 1,
-    /// The name server was unable to process this query due to a problem with the name server.
-    ServerFailure = 2,
-    /// Meaningful only for responses from an authoritative name server, this code signifies that the domain name referenced in the query does not exist.
-    NameError = 3,
-    /// The name server does not support the requested kind of query.
-    NotImplemented = 4,
-    /// The name server refuses to perform the specified operation for policy reasons.
+    /// No error condition (0)
+    NoError,
+    /// The name server was unable to interpret the query. (1)
+    FormatError,
+    /// The name server was unable to process this query due to a problem with the name server. (2)
+    ServerFailure,
+    /// Meaningful only for responses from an authoritative name server, this code signifies that the domain name referenced in the query does not exist. (3)
+    NameError,
+    /// The name server does not support the requested kind of query. (4)
+    NotImplemented,
+    /// The name server refuses to perform the specified operation for policy reasons. (5)
     /// For example, a name server may not wish to provide the information to the particular requester, or a name server may not wish to perform a particular operation (e.g., zone transfer) for particular data.
-    Refused = 5,
-    // 6-15 Reserved for future use.
+    Refused,
+    /// Some name that ought not to exist, does exist, RFC 2136 (6)
+    YXDomain,
+    /// Some RRset that ought not to exist, does exist, RFC 2136 (7)
+    YXRRSet,
+    /// Some RRset that ought to exist, does not exist, RFC 2136 (8)
+    NXRRSet,
+    /// The server is not authoritative for the zone named in the Zone Section, RFC 2136 (9)
+    NotAuth,
+    /// A name used in the update is not within the zone, RFC 2136 (10)
+    NotZone,
+    /// 11-15 are unassigned
+    Unknown(u8),
 }
 
 impl From<u8> for Opcode {
@@ -103,7 +128,36 @@ impl From<u8> for Opcode {
             0 => Opcode::QUERY,
             1 => Opcode::IQUERY,
             2 => Opcode::STATUS,
-            _ => panic!("Invalid opcode"),
+            4 => Opcode::NOTIFY,
+            5 => Opcode::UPDATE,
+            _ => Opcode::Unknown(byte),
+        }
+    }
+}
+
+impl Opcode {
+    pub fn to_num(&self) -> u8 {
+        match *self {
+            Opcode::QUERY => 0,
+            Opcode::IQUERY => 1,
+            Opcode::STATUS => 2,
+            Opcode::NOTIFY => 4,
+            Opcode::UPDATE => 5,
+            Opcode::Unknown(byte) => byte,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    /// The mnemonic dig prints, e.g. `QUERY`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Opcode::QUERY => f.write_str("QUERY"),
+            Opcode::IQUERY => f.write_str("IQUERY"),
+            Opcode::STATUS => f.write_str("STATUS"),
+            Opcode::NOTIFY => f.write_str("NOTIFY"),
+            Opcode::UPDATE => f.write_str("UPDATE"),
+            Opcode::Unknown(byte) => write!(f, "RESERVED{}", byte),
         }
     }
 }
@@ -117,7 +171,51 @@ impl From<u8> for RCode {
             3 => RCode::NameError,
             4 => RCode::NotImplemented,
             5 => RCode::Refused,
-            _ => panic!("Invalid rcode"),
+            6 => RCode::YXDomain,
+            7 => RCode::YXRRSet,
+            8 => RCode::NXRRSet,
+            9 => RCode::NotAuth,
+            10 => RCode::NotZone,
+            _ => RCode::Unknown(byte),
+        }
+    }
+}
+
+impl RCode {
+    pub fn to_num(&self) -> u8 {
+        match *self {
+            RCode::NoError => 0,
+            RCode::FormatError => 1,
+            RCode::ServerFailure => 2,
+            RCode::NameError => 3,
+            RCode::NotImplemented => 4,
+            RCode::Refused => 5,
+            RCode::YXDomain => 6,
+            RCode::YXRRSet => 7,
+            RCode::NXRRSet => 8,
+            RCode::NotAuth => 9,
+            RCode::NotZone => 10,
+            RCode::Unknown(byte) => byte,
+        }
+    }
+}
+
+impl fmt::Display for RCode {
+    /// The mnemonic dig prints, e.g. `NXDOMAIN`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RCode::NoError => f.write_str("NOERROR"),
+            RCode::FormatError => f.write_str("FORMERR"),
+            RCode::ServerFailure => f.write_str("SERVFAIL"),
+            RCode::NameError => f.write_str("NXDOMAIN"),
+            RCode::NotImplemented => f.write_str("NOTIMP"),
+            RCode::Refused => f.write_str("REFUSED"),
+            RCode::YXDomain => f.write_str("YXDOMAIN"),
+            RCode::YXRRSet => f.write_str("YXRRSET"),
+            RCode::NXRRSet => f.write_str("NXRRSET"),
+            RCode::NotAuth => f.write_str("NOTAUTH"),
+            RCode::NotZone => f.write_str("NOTZONE"),
+            RCode::Unknown(byte) => write!(f, "RCODE{}", byte),
         }
     }
 }
@@ -132,6 +230,9 @@ impl Default for Header {
             truncation: false,
             recursion_desired: false,
             recursion_available: false,
+            z: false,
+            authentic_data: false,
+            checking_disabled: false,
             rcode: RCode::NoError,
             question_count: 0,
             answer_count: 0,
@@ -142,7 +243,7 @@ impl Default for Header {
 }
 
 impl Header {
-    pub fn read(&mut self, buffer: &mut BytePacketBuffer) -> anyhow::Result<()> {
+    pub fn read(&mut self, buffer: &mut BytePacketBuffer) -> Result<(), ParseError> {
         self.id = buffer.read_u16()?;
 
         let flags = buffer.read_u16()?;
@@ -155,6 +256,9 @@ impl Header {
         self.is_reply = (a & (1 << 7)) > 0;
 
         self.rcode = (b & 0x0F).into();
+        self.checking_disabled = (b & (1 << 4)) > 0;
+        self.authentic_data = (b & (1 << 5)) > 0;
+        self.z = (b & (1 << 6)) > 0;
         self.recursion_available = (b & (1 << 7)) > 0;
 
         self.question_count = buffer.read_u16()?;
@@ -165,18 +269,24 @@ impl Header {
         Ok(())
     }
 
-    pub fn write(&self, buffer: &mut BytePacketBuffer) -> anyhow::Result<()> {
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<(), WriteError> {
         buffer.write_u16(self.id)?;
 
         buffer.write_u8(
             (self.recursion_desired as u8)
                 | ((self.truncation as u8) << 1)
                 | ((self.authoritative as u8) << 2)
-                | ((self.opcode as u8) << 3)
+                | ((self.opcode.to_num() & 0x0F) << 3)
                 | ((self.is_reply as u8) << 7),
         )?;
 
-        buffer.write_u8((self.rcode as u8) | ((self.recursion_available as u8) << 7))?;
+        buffer.write_u8(
+            (self.rcode.to_num() & 0x0F)
+                | ((self.checking_disabled as u8) << 4)
+                | ((self.authentic_data as u8) << 5)
+                | ((self.z as u8) << 6)
+                | ((self.recursion_available as u8) << 7),
+        )?;
 
         buffer.write_u16(self.question_count)?;
         buffer.write_u16(self.answer_count)?;
@@ -186,3 +296,70 @@ impl Header {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(header: &Header) -> (Vec<u8>, Header) {
+        let mut buffer = BytePacketBuffer::new();
+        header.write(&mut buffer).unwrap();
+        let bytes = buffer.filled().to_vec();
+
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut parsed = Header::default();
+        parsed.read(&mut buffer).unwrap();
+        (bytes, parsed)
+    }
+
+    #[test]
+    fn test_dnssec_bits_round_trip() {
+        let header = Header {
+            authentic_data: true,
+            ..Header::default()
+        };
+        let (bytes, parsed) = round_trip(&header);
+        assert_eq!(bytes[3], 0b0010_0000);
+        assert_eq!(parsed, header);
+
+        let header = Header {
+            checking_disabled: true,
+            ..Header::default()
+        };
+        let (bytes, parsed) = round_trip(&header);
+        assert_eq!(bytes[3], 0b0001_0000);
+        assert_eq!(parsed, header);
+
+        let header = Header {
+            z: true,
+            ..Header::default()
+        };
+        let (bytes, parsed) = round_trip(&header);
+        assert_eq!(bytes[3], 0b0100_0000);
+        assert_eq!(parsed, header);
+    }
+
+    #[test]
+    fn test_all_flags_round_trip() {
+        let header = Header {
+            id: 0xBEEF,
+            is_reply: true,
+            opcode: Opcode::NOTIFY,
+            authoritative: true,
+            truncation: true,
+            recursion_desired: true,
+            recursion_available: true,
+            z: true,
+            authentic_data: true,
+            checking_disabled: true,
+            rcode: RCode::Refused,
+            question_count: 1,
+            answer_count: 2,
+            authority_count: 3,
+            additional_count: 4,
+        };
+        let (bytes, parsed) = round_trip(&header);
+        assert_eq!(bytes[..4], [0xBE, 0xEF, 0b1010_0111, 0b1111_0101]);
+        assert_eq!(parsed, header);
+    }
+}
@@ -1,5 +1,19 @@
+pub mod authority;
+pub mod blocklist;
 pub mod byte_packet_buffer;
+pub mod cache;
+pub mod client;
+pub mod edns;
+pub mod error;
+pub mod forwarder;
 pub mod header;
+pub mod name;
 pub mod packet;
 pub mod question;
+mod random;
 pub mod record;
+pub mod resolver;
+pub mod server;
+pub mod srv;
+pub mod tcp;
+pub mod zone_file;
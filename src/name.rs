@@ -0,0 +1,138 @@
+// Helpers for comparing domain names in the form `read_qname` produces:
+// lowercase labels separated by dots, with no trailing dot, and the root
+// as the empty string.
+use std::cmp::Ordering;
+
+/// Bring a name from user input or a zone into the form `read_qname`
+/// produces, so that names can be compared with `==`.
+pub fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+/// Whether `name` is `ancestor` or lies below it. Every name is below the
+/// root.
+pub fn is_subdomain(name: &str, ancestor: &str) -> bool {
+    if ancestor.is_empty() {
+        return true;
+    }
+    if name.len() == ancestor.len() {
+        return name.eq_ignore_ascii_case(ancestor);
+    }
+    name.len() > ancestor.len()
+        && name.as_bytes()[name.len() - ancestor.len() - 1] == b'.'
+        && name[name.len() - ancestor.len()..].eq_ignore_ascii_case(ancestor)
+}
+
+/// The name with its first label removed, `None` for the root.
+pub fn parent(name: &str) -> Option<&str> {
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.split_once('.').map_or("", |(_, parent)| parent))
+}
+
+/// The name in presentation format (RFC 1035 §5.1): absolute, with a
+/// trailing dot, and with characters that would otherwise end or change the
+/// meaning of a label escaped.
+pub fn to_presentation(name: &str) -> String {
+    if name.is_empty() {
+        return ".".to_string();
+    }
+
+    let mut out = String::with_capacity(name.len() + 1);
+    for label in name.split('.') {
+        for &b in label.as_bytes() {
+            escape_byte(b, &mut out);
+        }
+        out.push('.');
+    }
+    out
+}
+
+/// Append a byte of a label or character-string to `out`, escaped if it
+/// isn't printable ASCII or has a special meaning in a zone file.
+pub(crate) fn escape_byte(b: u8, out: &mut String) {
+    match b {
+        b'.' | b'"' | b'(' | b')' | b';' | b'@' | b'$' | b'\\' => {
+            out.push('\\');
+            out.push(b as char);
+        }
+        0x21..=0x7E => out.push(b as char),
+        _ => out.push_str(&format!("\\{:03}", b)),
+    }
+}
+
+/// Compare names in the canonical order of RFC 4034 §6.1: label by label
+/// starting from the root, so that a name sorts right after its ancestors.
+pub fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    let labels = |name: &str| -> Vec<String> {
+        if name.is_empty() {
+            return Vec::new();
+        }
+        name.rsplit('.').map(|label| label.to_lowercase()).collect()
+    };
+    labels(a).cmp(&labels(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("WWW.Example.COM."), "www.example.com");
+        assert_eq!(normalize("."), "");
+    }
+
+    #[test]
+    fn test_is_subdomain() {
+        assert!(is_subdomain("www.example.com", "example.com"));
+        assert!(is_subdomain("example.com", "example.com"));
+        assert!(is_subdomain("WWW.example.com", "Example.com"));
+        assert!(is_subdomain("example.com", ""));
+        assert!(!is_subdomain("badexample.com", "example.com"));
+        assert!(!is_subdomain("com", "example.com"));
+        assert!(!is_subdomain("", "com"));
+    }
+
+    #[test]
+    fn test_parent() {
+        assert_eq!(parent("www.example.com"), Some("example.com"));
+        assert_eq!(parent("com"), Some(""));
+        assert_eq!(parent(""), None);
+    }
+
+    #[test]
+    fn test_to_presentation() {
+        assert_eq!(to_presentation("www.example.com"), "www.example.com.");
+        assert_eq!(to_presentation(""), ".");
+        assert_eq!(to_presentation("a b;c.example"), "a\\032b\\;c.example.");
+    }
+
+    #[test]
+    fn test_canonical_cmp() {
+        // The example from RFC 4034 §6.1, less the names with escapes
+        let mut names = vec![
+            "z.example",
+            "zabc.a.example",
+            "yljkjljk.a.example",
+            "example",
+            "a.example",
+            "*.z.example",
+            "z.a.example",
+        ];
+        names.sort_by(|a, b| canonical_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "example",
+                "a.example",
+                "yljkjljk.a.example",
+                "z.a.example",
+                "zabc.a.example",
+                "z.example",
+                "*.z.example",
+            ]
+        );
+    }
+}
@@ -11,8 +11,10 @@
 //     |      Additional     | RRs holding additional information
 //     +---------------------+
 use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::edns::Opt;
+use crate::error::{ParseError, WriteError};
 use crate::header::Header;
-use crate::question::Question;
+use crate::question::{QType, Question};
 use crate::record::Record;
 
 #[derive(Clone, Debug, Default)]
@@ -20,10 +22,15 @@ pub struct Packet {
     pub header: Header,
     pub questions: Vec<Question>,
     pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub resources: Vec<Record>,
+    /// The EDNS(0) OPT record, which is read from and written to the
+    /// additional section but kept apart from the other resources
+    pub edns: Option<Opt>,
 }
 
 impl Packet {
-    pub fn from_buffer(buffer: &mut BytePacketBuffer) -> anyhow::Result<Packet> {
+    pub fn from_buffer(buffer: &mut BytePacketBuffer) -> Result<Packet, ParseError> {
         let mut result = Packet::default();
         result.header.read(buffer)?;
 
@@ -37,15 +44,37 @@ impl Packet {
             let rec = Record::read(buffer)?;
             result.answers.push(rec);
         }
+        for _ in 0..result.header.authority_count {
+            let rec = Record::read(buffer)?;
+            result.authorities.push(rec);
+        }
+        for _ in 0..result.header.additional_count {
+            // Peek at the type to tell the OPT pseudo-record apart
+            let start = buffer.pos();
+            let mut domain = String::new();
+            buffer.read_qname(&mut domain)?;
+            let qtype: QType = buffer.read_u16()?.into();
+            buffer.seek(start)?;
+
+            if qtype == QType::OPT {
+                if result.edns.is_some() {
+                    return Err(ParseError::DuplicateOpt { offset: start });
+                }
+                result.edns = Some(Opt::read(buffer)?);
+            } else {
+                let rec = Record::read(buffer)?;
+                result.resources.push(rec);
+            }
+        }
 
         Ok(result)
     }
 
-    pub fn write(&mut self, buffer: &mut BytePacketBuffer) -> anyhow::Result<()> {
+    pub fn write(&mut self, buffer: &mut BytePacketBuffer) -> Result<(), WriteError> {
         self.header.question_count = self.questions.len() as u16;
         self.header.answer_count = self.answers.len() as u16;
-        self.header.authority_count = 0;
-        self.header.additional_count = 0;
+        self.header.authority_count = self.authorities.len() as u16;
+        self.header.additional_count = self.resources.len() as u16 + self.edns.is_some() as u16;
 
         self.header.write(buffer)?;
 
@@ -55,17 +84,53 @@ impl Packet {
         for rec in &self.answers {
             rec.write(buffer)?;
         }
+        for rec in &self.authorities {
+            rec.write(buffer)?;
+        }
+        for rec in &self.resources {
+            rec.write(buffer)?;
+        }
+        if let Some(ref opt) = self.edns {
+            opt.write(buffer)?;
+        }
 
         Ok(())
     }
+
+    /// The full 12 bit response code: the header's 4 bit RCODE extended
+    /// with the upper 8 bits from the OPT record, if there is one
+    pub fn response_code(&self) -> u16 {
+        let extended = self.edns.as_ref().map_or(0, |opt| opt.extended_rcode);
+        ((extended as u16) << 4) | (self.header.rcode.to_num() & 0x0F) as u16
+    }
+
+    /// Split a 12 bit response code between the header and the OPT record.
+    /// Codes above 15, e.g. BADVERS (16), need an OPT record, so one is
+    /// added if the packet doesn't have one yet.
+    pub fn set_response_code(&mut self, code: u16) {
+        self.header.rcode = ((code & 0x0F) as u8).into();
+
+        let extended = ((code >> 4) & 0xFF) as u8;
+        match self.edns {
+            Some(ref mut opt) => opt.extended_rcode = extended,
+            None if extended != 0 => {
+                self.edns = Some(Opt {
+                    extended_rcode: extended,
+                    ..Opt::default()
+                })
+            }
+            None => {}
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::byte_packet_buffer::MAX_SIZE;
     use crate::header::{Opcode, RCode};
     use crate::question::{QClass, QType};
-    use std::net::Ipv4Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn test_default_packet() {
@@ -73,6 +138,9 @@ mod tests {
         assert_eq!(packet.header, Header::default());
         assert_eq!(packet.questions, Vec::default());
         assert_eq!(packet.answers, Vec::default());
+        assert_eq!(packet.authorities, Vec::default());
+        assert_eq!(packet.resources, Vec::default());
+        assert_eq!(packet.edns, None);
     }
 
     #[test]
@@ -104,19 +172,19 @@ mod tests {
 
     #[test]
     fn test_standard_packet_from_bytes() {
-        let mut bytes = build_standard_packet_bytes();
+        let bytes = build_standard_packet_bytes();
         let mut buffer = BytePacketBuffer::new();
-        buffer.buf[..bytes.len()].copy_from_slice(&mut bytes);
+        buffer.buf[..bytes.len()].copy_from_slice(&bytes);
 
         let packet = Packet::from_buffer(&mut buffer).unwrap();
 
         assert_eq!(packet.header.id, 1234);
-        assert_eq!(packet.header.is_reply, true);
+        assert!(packet.header.is_reply);
         assert_eq!(packet.header.opcode, Opcode::QUERY);
-        assert_eq!(packet.header.authoritative, false);
-        assert_eq!(packet.header.truncation, false);
-        assert_eq!(packet.header.recursion_desired, false);
-        assert_eq!(packet.header.recursion_available, false);
+        assert!(!packet.header.authoritative);
+        assert!(!packet.header.truncation);
+        assert!(!packet.header.recursion_desired);
+        assert!(!packet.header.recursion_available);
         assert_eq!(packet.header.rcode, RCode::NoError);
         assert_eq!(packet.header.question_count, 1);
         assert_eq!(packet.header.answer_count, 1);
@@ -127,8 +195,14 @@ mod tests {
         assert_eq!(packet.questions[0].qtype, QType::A);
         assert_eq!(packet.questions[0].qclass, QClass::IN);
         match &packet.answers[0] {
-            Record::A { domain, addr, ttl } => {
+            Record::A {
+                domain,
+                addr,
+                class,
+                ttl,
+            } => {
                 assert_eq!(domain, "codecrafters.io");
+                assert_eq!(class, &QClass::IN);
                 assert_eq!(addr, &Ipv4Addr::new(8, 8, 8, 8));
                 assert_eq!(ttl, &60);
             }
@@ -142,9 +216,462 @@ mod tests {
             buffer.buf[..buffer.pos],
             [
                 4, 210, 128, 0, 0, 1, 0, 1, 0, 0, 0, 0, 12, 99, 111, 100, 101, 99, 114, 97, 102,
-                116, 101, 114, 115, 2, 105, 111, 0, 0, 1, 0, 1, 12, 99, 111, 100, 101, 99, 114, 97,
-                102, 116, 101, 114, 115, 2, 105, 111, 0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 8, 8, 8, 8
+                116, 101, 114, 115, 2, 105, 111, 0, 0, 1, 0, 1, // question
+                0xC0, 12, // rr name, compressed to point at the question
+                0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 8, 8, 8, 8
             ]
         );
     }
+
+    #[test]
+    fn test_packet_larger_than_512_bytes() {
+        let mut packet = Packet::default();
+        packet.questions.push(Question {
+            name: "codecrafters.io".to_string(),
+            qtype: QType::A,
+            qclass: QClass::IN,
+        });
+        for i in 0..40 {
+            packet.answers.push(Record::A {
+                domain: "codecrafters.io".to_string(),
+                addr: Ipv4Addr::new(10, 0, 0, i),
+                class: QClass::IN,
+                ttl: 60,
+            });
+        }
+
+        let mut buffer = BytePacketBuffer::new();
+        assert!(packet.write(&mut buffer).is_err());
+
+        let mut buffer = BytePacketBuffer::with_max_size(MAX_SIZE);
+        packet.write(&mut buffer).unwrap();
+        assert!(buffer.pos() > 512);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        let parsed = Packet::from_buffer(&mut buffer).unwrap();
+        assert_eq!(parsed.header.answer_count, 40);
+        assert_eq!(parsed.questions, packet.questions);
+        assert_eq!(parsed.answers, packet.answers);
+    }
+
+    #[test]
+    fn test_parse_error_offset() {
+        let bytes = build_standard_packet_bytes();
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes[..60]);
+        let err = Packet::from_buffer(&mut buffer).unwrap_err();
+        assert_eq!(err, ParseError::UnexpectedEnd { offset: 60 });
+        assert_eq!(err.offset(), 60);
+    }
+
+    #[test]
+    fn test_unknown_values_round_trip() {
+        let mut bytes = build_standard_packet_bytes();
+        bytes[2] |= 5 << 3; // UPDATE
+        bytes[3] |= 11; // unassigned rcode
+        bytes[29] = 0xFF; // private type 0xFF01
+        bytes[31] = 0xFF; // private class 0xFF01
+        bytes[51] = 99; // unassigned record type
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut packet = Packet::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(packet.header.opcode, Opcode::UPDATE);
+        assert_eq!(packet.header.rcode, RCode::Unknown(11));
+        assert_eq!(packet.questions[0].qtype, QType::Unknown(0xFF01));
+        assert_eq!(packet.questions[0].qclass, QClass::Unknown(0xFF01));
+        assert_eq!(
+            packet.answers[0],
+            Record::Unknown {
+                domain: "codecrafters.io".to_string(),
+                qtype: 99,
+                class: QClass::IN,
+                data: vec![8, 8, 8, 8],
+                ttl: 60,
+            }
+        );
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(buffer.buf[..33], bytes[..33]);
+        assert_eq!(buffer.filled()[35..], bytes[50..]);
+    }
+
+    #[test]
+    fn test_opcode_and_rcode_are_lossless() {
+        for byte in 0..16 {
+            assert_eq!(Opcode::from(byte).to_num(), byte);
+            assert_eq!(RCode::from(byte).to_num(), byte);
+        }
+        for byte in [0, 1, 6, 16, 28, 252, 255, 256, 65535] {
+            assert_eq!(QType::from(byte).to_num(), byte);
+            assert_eq!(QClass::from(byte).to_num(), byte);
+        }
+    }
+
+    #[test]
+    fn test_authority_and_additional_sections() {
+        let mut packet = Packet::default();
+        packet.questions.push(Question {
+            name: "www.codecrafters.io".to_string(),
+            qtype: QType::A,
+            qclass: QClass::IN,
+        });
+        packet.authorities.push(Record::NS {
+            domain: "codecrafters.io".to_string(),
+            host: "ns1.codecrafters.io".to_string(),
+            class: QClass::IN,
+            ttl: 3600,
+        });
+        packet.authorities.push(Record::NS {
+            domain: "codecrafters.io".to_string(),
+            host: "ns2.codecrafters.io".to_string(),
+            class: QClass::IN,
+            ttl: 3600,
+        });
+        packet.resources.push(Record::A {
+            domain: "ns1.codecrafters.io".to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+            class: QClass::IN,
+            ttl: 3600,
+        });
+        packet.resources.push(Record::A {
+            domain: "ns2.codecrafters.io".to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, 2),
+            class: QClass::IN,
+            ttl: 3600,
+        });
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(packet.header.answer_count, 0);
+        assert_eq!(packet.header.authority_count, 2);
+        assert_eq!(packet.header.additional_count, 2);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        let parsed = Packet::from_buffer(&mut buffer).unwrap();
+        assert_eq!(parsed.header, packet.header);
+        assert_eq!(parsed.answers, Vec::default());
+        assert_eq!(parsed.authorities, packet.authorities);
+        assert_eq!(parsed.resources, packet.resources);
+    }
+
+    fn build_aaaa_packet_bytes() -> Vec<u8> {
+        vec![
+            4, 210, 128, 0, 0, 1, 0, 1, 0, 0, 0, 0, // header
+            12, 99, 111, 100, 101, 99, 114, 97, 102, 116, 101, 114, 115, 2, 105, 111,
+            0, // qname
+            0, 28, // qtype
+            0, 1, // qclass
+            0xC0, 12, // rr name
+            0, 28, // rr type
+            0, 1, // rr class
+            0, 0, 0, 60, // ttl
+            0, 16, // rdlength
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, // rdata
+        ]
+    }
+
+    #[test]
+    fn test_aaaa_packet_from_bytes() {
+        let bytes = build_aaaa_packet_bytes();
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut packet = Packet::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(packet.questions[0].qtype, QType::AAAA);
+        assert_eq!(
+            packet.answers[0],
+            Record::AAAA {
+                domain: "codecrafters.io".to_string(),
+                addr: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+                class: QClass::IN,
+                ttl: 60,
+            }
+        );
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(buffer.filled(), bytes);
+    }
+
+    #[test]
+    fn test_aaaa_with_wrong_rdlength() {
+        let mut bytes = build_aaaa_packet_bytes();
+        bytes[44] = 4;
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        assert_eq!(
+            Packet::from_buffer(&mut buffer).unwrap_err(),
+            ParseError::InvalidRdata {
+                offset: 45,
+                qtype: 28,
+                reason: "an IPv6 address must be 16 bytes",
+            }
+        );
+    }
+
+    #[test]
+    fn test_name_valued_records_from_bytes() {
+        let bytes = [
+            4, 210, 128, 0, 0, 1, 0, 3, 0, 0, 0, 0, // header
+            3, 119, 119, 119, 12, 99, 111, 100, 101, 99, 114, 97, 102, 116, 101, 114, 115, 2, 105,
+            111, 0, // www.codecrafters.io
+            0, 1, 0, 1, // qtype, qclass
+            0xC0, 12, // www.codecrafters.io
+            0, 5, 0, 1, 0, 0, 0, 60, 0, 6, // CNAME, IN, ttl, rdlength
+            3, 99, 100, 110, 0xC0, 16, // cdn.codecrafters.io
+            0xC0, 16, // codecrafters.io
+            0, 2, 0, 1, 0, 0, 14, 16, 0, 6, // NS, IN, ttl, rdlength
+            3, 110, 115, 49, 0xC0, 16, // ns1.codecrafters.io
+            1, 52, 1, 50, 1, 48, 3, 49, 57, 50, 7, 105, 110, 45, 97, 100, 100, 114, 4, 97, 114,
+            112, 97, 0, // 4.2.0.192.in-addr.arpa
+            0, 12, 0, 1, 0, 0, 0, 60, 0, 2, // PTR, IN, ttl, rdlength
+            0xC0, 16, // codecrafters.io
+        ];
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut packet = Packet::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(
+            packet.answers,
+            vec![
+                Record::CNAME {
+                    domain: "www.codecrafters.io".to_string(),
+                    host: "cdn.codecrafters.io".to_string(),
+                    class: QClass::IN,
+                    ttl: 60,
+                },
+                Record::NS {
+                    domain: "codecrafters.io".to_string(),
+                    host: "ns1.codecrafters.io".to_string(),
+                    class: QClass::IN,
+                    ttl: 3600,
+                },
+                Record::PTR {
+                    domain: "4.2.0.192.in-addr.arpa".to_string(),
+                    host: "codecrafters.io".to_string(),
+                    class: QClass::IN,
+                    ttl: 60,
+                },
+            ]
+        );
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(buffer.filled(), bytes);
+    }
+
+    #[test]
+    fn test_mx_and_soa_packet_from_bytes() {
+        let bytes = [
+            4, 210, 133, 3, 0, 1, 0, 1, 0, 1, 0, 0, // header, NXDOMAIN
+            12, 99, 111, 100, 101, 99, 114, 97, 102, 116, 101, 114, 115, 2, 105, 111,
+            0, // codecrafters.io
+            0, 15, 0, 1, // qtype, qclass
+            0xC0, 12, // codecrafters.io
+            0, 15, 0, 1, 0, 0, 1, 44, 0, 9, // MX, IN, ttl, rdlength
+            0, 10, // priority
+            4, 109, 97, 105, 108, 0xC0, 12, // mail.codecrafters.io
+            0xC0, 12, // codecrafters.io
+            0, 6, 0, 1, 0, 0, 3, 132, 0, 39, // SOA, IN, ttl, rdlength
+            3, 110, 115, 49, 0xC0, 12, // ns1.codecrafters.io
+            10, 104, 111, 115, 116, 109, 97, 115, 116, 101, 114, 0xC0,
+            12, // hostmaster.codecrafters.io
+            120, 150, 99, 37, // serial
+            0, 0, 28, 32, // refresh
+            0, 0, 14, 16, // retry
+            0, 18, 117, 0, // expire
+            0, 0, 3, 132, // minimum
+        ];
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut packet = Packet::from_buffer(&mut buffer).unwrap();
+
+        assert!(packet.header.authoritative);
+        assert_eq!(packet.header.rcode, RCode::NameError);
+        assert_eq!(packet.questions[0].qtype, QType::MX);
+        assert_eq!(
+            packet.answers,
+            vec![Record::MX {
+                domain: "codecrafters.io".to_string(),
+                priority: 10,
+                host: "mail.codecrafters.io".to_string(),
+                class: QClass::IN,
+                ttl: 300,
+            }]
+        );
+        assert_eq!(
+            packet.authorities,
+            vec![Record::SOA {
+                domain: "codecrafters.io".to_string(),
+                mname: "ns1.codecrafters.io".to_string(),
+                rname: "hostmaster.codecrafters.io".to_string(),
+                serial: 2023121701,
+                refresh: 7200,
+                retry: 3600,
+                expire: 1209600,
+                minimum: 900,
+                class: QClass::IN,
+                ttl: 900,
+            }]
+        );
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(buffer.filled(), bytes);
+    }
+
+    #[test]
+    fn test_txt_packet_from_bytes() {
+        let bytes = [
+            4, 210, 128, 0, 0, 1, 0, 1, 0, 0, 0, 0, // header
+            12, 99, 111, 100, 101, 99, 114, 97, 102, 116, 101, 114, 115, 2, 105, 111,
+            0, // codecrafters.io
+            0, 16, 0, 1, // qtype, qclass
+            0xC0, 12, // codecrafters.io
+            0, 16, 0, 1, 0, 0, 0, 60, 0, 23, // TXT, IN, ttl, rdlength
+            11, b'v', b'=', b's', b'p', b'f', b'1', b' ', b'-', b'a', b'l',
+            b'l', // "v=spf1 -all"
+            0,    // ""
+            9, b'h', b'e', b'l', b'l', b'o', b' ', b'"', b'x', b'"', // "hello \"x\""
+        ];
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut packet = Packet::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(
+            packet.answers,
+            vec![Record::TXT {
+                domain: "codecrafters.io".to_string(),
+                data: vec![b"v=spf1 -all".to_vec(), vec![], b"hello \"x\"".to_vec()],
+                class: QClass::IN,
+                ttl: 60,
+            }]
+        );
+        assert_eq!(
+            packet.answers[0].txt_joined().unwrap(),
+            "v=spf1 -allhello \"x\""
+        );
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(buffer.filled(), bytes);
+    }
+
+    #[test]
+    fn test_txt_character_string_limits() {
+        let key = "p=".to_string() + &"A".repeat(400);
+        let data = Record::txt_split(&key);
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].len(), 255);
+        assert_eq!(data[1].len(), 147);
+
+        let record = Record::TXT {
+            domain: "selector._domainkey.codecrafters.io".to_string(),
+            data,
+            class: QClass::IN,
+            ttl: 60,
+        };
+        assert_eq!(record.txt_joined().unwrap(), key);
+
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(Record::read(&mut buffer).unwrap(), record);
+
+        let record = Record::TXT {
+            domain: "codecrafters.io".to_string(),
+            data: vec![key.into_bytes()],
+            class: QClass::IN,
+            ttl: 60,
+        };
+        let mut buffer = BytePacketBuffer::new();
+        assert_eq!(
+            record.write(&mut buffer),
+            Err(WriteError::CharacterStringTooLong { len: 402 })
+        );
+    }
+
+    #[test]
+    fn test_txt_overrunning_rdata() {
+        let bytes = [
+            0, 0, 16, 0, 1, 0, 0, 0, 60, 0, 3, // root, TXT, IN, ttl, rdlength
+            3, b'a', b'b', b'c',
+        ];
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        assert_eq!(
+            Record::read(&mut buffer),
+            Err(ParseError::InvalidRdata {
+                offset: 11,
+                qtype: 16,
+                reason: "character-string overruns the rdata",
+            })
+        );
+    }
+
+    #[test]
+    fn test_edns_in_additional_section() {
+        let mut packet = Packet::default();
+        packet.questions.push(Question {
+            name: "codecrafters.io".to_string(),
+            qtype: QType::A,
+            qclass: QClass::IN,
+        });
+        packet.resources.push(Record::A {
+            domain: "ns1.codecrafters.io".to_string(),
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+            class: QClass::IN,
+            ttl: 3600,
+        });
+        packet.edns = Some(Opt {
+            udp_payload_size: 4096,
+            dnssec_ok: true,
+            ..Opt::default()
+        });
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(packet.header.additional_count, 2);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        let parsed = Packet::from_buffer(&mut buffer).unwrap();
+        assert_eq!(parsed.resources, packet.resources);
+        assert_eq!(parsed.edns, packet.edns);
+    }
+
+    #[test]
+    fn test_duplicate_opt() {
+        let mut packet = Packet::default();
+        packet.resources.push(Record::Unknown {
+            domain: "".to_string(),
+            qtype: 41,
+            class: QClass::Unknown(512),
+            data: vec![],
+            ttl: 0,
+        });
+        packet.edns = Some(Opt::default());
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(
+            Packet::from_buffer(&mut buffer).unwrap_err(),
+            ParseError::DuplicateOpt { offset: 23 }
+        );
+    }
+
+    #[test]
+    fn test_extended_response_code() {
+        let mut packet = Packet::default();
+        packet.set_response_code(RCode::NameError.to_num() as u16);
+        assert_eq!(packet.header.rcode, RCode::NameError);
+        assert_eq!(packet.edns, None);
+        assert_eq!(packet.response_code(), 3);
+
+        // BADVERS
+        packet.set_response_code(16);
+        assert_eq!(packet.header.rcode, RCode::NoError);
+        assert_eq!(packet.edns.as_ref().unwrap().extended_rcode, 1);
+        assert_eq!(packet.response_code(), 16);
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        let parsed = Packet::from_buffer(&mut buffer).unwrap();
+        assert_eq!(parsed.response_code(), 16);
+    }
 }
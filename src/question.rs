@@ -10,6 +10,8 @@
 // |                     QCLASS                    |
 // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::{ParseError, WriteError};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Question {
@@ -18,50 +20,58 @@ pub struct Question {
     pub qclass: QClass,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum QType {
-    /// a host address
-    A = 1,
-    /// an authoritative name server
-    NS = 2,
-    /// a mail destination (Obsolete - use MX)
-    MD = 3,
-    /// a mail forwarder (Obsolete - use MX)
-    MF = 4,
-    /// the canonical name for an alias
-    CNAME = 5,
-    /// marks the start of a zone of authority
-    SOA = 6,
-    /// a mailbox domain name (EXPERIMENTAL)
-    MB = 7,
-    /// a mail group member (EXPERIMENTAL)
-    MG = 8,
-    /// a mail rename domain name (EXPERIMENTAL)
-    MR = 9,
-    /// a null RR (EXPERIMENTAL)
-    NULL = 10,
-    /// a well known service description
-    WKS = 11,
-    /// a domain name pointer
-    PTR = 12,
-    /// host information
-    HINFO = 13,
-    /// mailbox or mail list information
-    MINFO = 14,
-    /// mail exchange
-    MX = 15,
-    /// text strings
-    TXT = 16,
+    /// a host address (1)
+    A,
+    /// an authoritative name server (2)
+    NS,
+    /// a mail destination (Obsolete - use MX) (3)
+    MD,
+    /// a mail forwarder (Obsolete - use MX) (4)
+    MF,
+    /// the canonical name for an alias (5)
+    CNAME,
+    /// marks the start of a zone of authority (6)
+    SOA,
+    /// a mailbox domain name (EXPERIMENTAL) (7)
+    MB,
+    /// a mail group member (EXPERIMENTAL) (8)
+    MG,
+    /// a mail rename domain name (EXPERIMENTAL) (9)
+    MR,
+    /// a null RR (EXPERIMENTAL) (10)
+    NULL,
+    /// a well known service description (11)
+    WKS,
+    /// a domain name pointer (12)
+    PTR,
+    /// host information (13)
+    HINFO,
+    /// mailbox or mail list information (14)
+    MINFO,
+    /// mail exchange (15)
+    MX,
+    /// text strings (16)
+    TXT,
+    /// an IPv6 host address, RFC 3596 (28)
+    AAAA,
+    /// the location of a service, RFC 2782 (33)
+    SRV,
+    /// the EDNS(0) pseudo-record, RFC 6891 (41)
+    OPT,
 
     // QTYPE specific
-    /// A request for a transfer of an entire zone
-    AXFR = 252,
-    /// A request for mailbox-related records (MB, MG or MR)
-    MAILB = 253,
-    ///  A request for mail agent RRs (Obsolete - see MX)
-    MAILA = 254,
-    /// A request for all records
-    ANY = 255,
+    /// A request for a transfer of an entire zone (252)
+    AXFR,
+    /// A request for mailbox-related records (MB, MG or MR) (253)
+    MAILB,
+    /// A request for mail agent RRs (Obsolete - see MX) (254)
+    MAILA,
+    /// A request for all records (255)
+    ANY,
+    /// any type this crate doesn't know, kept so it can be written back
+    Unknown(u16),
 }
 
 impl From<u16> for QType {
@@ -83,15 +93,114 @@ impl From<u16> for QType {
             14 => QType::MINFO,
             15 => QType::MX,
             16 => QType::TXT,
+            28 => QType::AAAA,
+            33 => QType::SRV,
+            41 => QType::OPT,
             252 => QType::AXFR,
             253 => QType::MAILB,
             254 => QType::MAILA,
             255 => QType::ANY,
-            _ => panic!("Invalid qtype"),
+            _ => QType::Unknown(byte),
         }
     }
 }
 
+impl QType {
+    pub fn to_num(&self) -> u16 {
+        match *self {
+            QType::A => 1,
+            QType::NS => 2,
+            QType::MD => 3,
+            QType::MF => 4,
+            QType::CNAME => 5,
+            QType::SOA => 6,
+            QType::MB => 7,
+            QType::MG => 8,
+            QType::MR => 9,
+            QType::NULL => 10,
+            QType::WKS => 11,
+            QType::PTR => 12,
+            QType::HINFO => 13,
+            QType::MINFO => 14,
+            QType::MX => 15,
+            QType::TXT => 16,
+            QType::AAAA => 28,
+            QType::SRV => 33,
+            QType::OPT => 41,
+            QType::AXFR => 252,
+            QType::MAILB => 253,
+            QType::MAILA => 254,
+            QType::ANY => 255,
+            QType::Unknown(byte) => byte,
+        }
+    }
+
+    /// The type named by a zone file mnemonic such as `MX`, or by the
+    /// `TYPE<n>` form RFC 3597 §5 allows for any type. Case insensitive.
+    pub fn from_mnemonic(text: &str) -> Option<QType> {
+        let qtype = match text.to_ascii_uppercase().as_str() {
+            "A" => QType::A,
+            "NS" => QType::NS,
+            "MD" => QType::MD,
+            "MF" => QType::MF,
+            "CNAME" => QType::CNAME,
+            "SOA" => QType::SOA,
+            "MB" => QType::MB,
+            "MG" => QType::MG,
+            "MR" => QType::MR,
+            "NULL" => QType::NULL,
+            "WKS" => QType::WKS,
+            "PTR" => QType::PTR,
+            "HINFO" => QType::HINFO,
+            "MINFO" => QType::MINFO,
+            "MX" => QType::MX,
+            "TXT" => QType::TXT,
+            "AAAA" => QType::AAAA,
+            "SRV" => QType::SRV,
+            "OPT" => QType::OPT,
+            "AXFR" => QType::AXFR,
+            "MAILB" => QType::MAILB,
+            "MAILA" => QType::MAILA,
+            "ANY" | "*" => QType::ANY,
+            upper => upper.strip_prefix("TYPE")?.parse::<u16>().ok()?.into(),
+        };
+        Some(qtype)
+    }
+}
+
+impl fmt::Display for QType {
+    /// The mnemonic used in zone files, or `TYPE<n>` for types without one
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = match *self {
+            QType::A => "A",
+            QType::NS => "NS",
+            QType::MD => "MD",
+            QType::MF => "MF",
+            QType::CNAME => "CNAME",
+            QType::SOA => "SOA",
+            QType::MB => "MB",
+            QType::MG => "MG",
+            QType::MR => "MR",
+            QType::NULL => "NULL",
+            QType::WKS => "WKS",
+            QType::PTR => "PTR",
+            QType::HINFO => "HINFO",
+            QType::MINFO => "MINFO",
+            QType::MX => "MX",
+            QType::TXT => "TXT",
+            QType::AAAA => "AAAA",
+            QType::SRV => "SRV",
+            QType::OPT => "OPT",
+            QType::AXFR => "AXFR",
+            QType::MAILB => "MAILB",
+            QType::MAILA => "MAILA",
+            QType::ANY => "ANY",
+            QType::Unknown(num) => return write!(f, "TYPE{}", num),
+        };
+        f.write_str(mnemonic)
+    }
+}
+
 impl From<u16> for QClass {
     fn from(byte: u16) -> Self {
         match byte {
@@ -100,24 +209,69 @@ impl From<u16> for QClass {
             3 => QClass::CH,
             4 => QClass::HS,
             255 => QClass::Any,
-            _ => panic!("Invalid qclass"),
+            _ => QClass::Unknown(byte),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl QClass {
+    pub fn to_num(&self) -> u16 {
+        match *self {
+            QClass::IN => 1,
+            QClass::CS => 2,
+            QClass::CH => 3,
+            QClass::HS => 4,
+            QClass::Any => 255,
+            QClass::Unknown(byte) => byte,
+        }
+    }
+
+    /// The class named by a zone file mnemonic such as `IN`, or by the
+    /// `CLASS<n>` form of RFC 3597 §5. Case insensitive.
+    pub fn from_mnemonic(text: &str) -> Option<QClass> {
+        let class = match text.to_ascii_uppercase().as_str() {
+            "IN" => QClass::IN,
+            "CS" => QClass::CS,
+            "CH" => QClass::CH,
+            "HS" => QClass::HS,
+            "ANY" => QClass::Any,
+            upper => upper.strip_prefix("CLASS")?.parse::<u16>().ok()?.into(),
+        };
+        Some(class)
+    }
+}
+
+impl fmt::Display for QClass {
+    /// The mnemonic used in zone files, or `CLASS<n>` for classes without
+    /// one
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = match *self {
+            QClass::IN => "IN",
+            QClass::CS => "CS",
+            QClass::CH => "CH",
+            QClass::HS => "HS",
+            QClass::Any => "ANY",
+            QClass::Unknown(num) => return write!(f, "CLASS{}", num),
+        };
+        f.write_str(mnemonic)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum QClass {
-    /// the Internet
-    IN = 1,
-    /// the CSNET class (Obsolete - used only for examples in some obsolete RFCs)
-    CS = 2,
-    /// the CHAOS class
-    CH = 3,
-    /// Hesiod [Dyer 87]
-    HS = 4,
+    /// the Internet (1)
+    IN,
+    /// the CSNET class (Obsolete - used only for examples in some obsolete RFCs) (2)
+    CS,
+    /// the CHAOS class (3)
+    CH,
+    /// Hesiod [Dyer 87] (4)
+    HS,
     // QCLASS specific
-    /// any class
-    Any = 255,
+    /// any class (255)
+    Any,
+    /// any class this crate doesn't know, e.g. a private use class
+    Unknown(u16),
 }
 
 impl Default for Question {
@@ -131,19 +285,20 @@ impl Default for Question {
 }
 
 impl Question {
-    pub fn read(&mut self, buffer: &mut BytePacketBuffer) -> anyhow::Result<()> {
+    pub fn read(&mut self, buffer: &mut BytePacketBuffer) -> Result<(), ParseError> {
         buffer.read_qname(&mut self.name)?;
+
         self.qtype = buffer.read_u16()?.into();
         self.qclass = buffer.read_u16()?.into();
 
         Ok(())
     }
 
-    pub fn write(&self, buffer: &mut BytePacketBuffer) -> anyhow::Result<()> {
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<(), WriteError> {
         buffer.write_qname(&self.name)?;
 
-        buffer.write_u16(self.qtype as u16)?;
-        buffer.write_u16(self.qclass as u16)?;
+        buffer.write_u16(self.qtype.to_num())?;
+        buffer.write_u16(self.qclass.to_num())?;
 
         Ok(())
     }
@@ -0,0 +1,9 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// A random number good enough for load balancing and query ids, without
+/// pulling in a dependency. Every `RandomState` is seeded with fresh keys, so
+/// hashing nothing with it yields a new pseudorandom value.
+pub(crate) fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
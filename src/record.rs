@@ -19,37 +19,110 @@
 //     /                                               /
 //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 use crate::byte_packet_buffer::BytePacketBuffer;
-use crate::question::QType;
-use std::net::Ipv4Addr;
+use crate::error::{ParseError, WriteError};
+use crate::name::to_presentation;
+use crate::question::{QClass, QType};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Record {
+    /// Any type this crate doesn't interpret, kept as raw RDATA so that it
+    /// can be written back exactly as it was read (RFC 3597)
     Unknown {
         domain: String,
         qtype: u16,
-        data_len: u16,
+        class: QClass,
+        data: Vec<u8>,
         ttl: u32,
     },
     A {
         domain: String,
         addr: Ipv4Addr,
+        class: QClass,
+        ttl: u32,
+    },
+    AAAA {
+        domain: String,
+        addr: Ipv6Addr,
+        class: QClass,
+        ttl: u32,
+    },
+    NS {
+        domain: String,
+        host: String,
+        class: QClass,
+        ttl: u32,
+    },
+    CNAME {
+        domain: String,
+        host: String,
+        class: QClass,
+        ttl: u32,
+    },
+    PTR {
+        domain: String,
+        host: String,
+        class: QClass,
+        ttl: u32,
+    },
+    MX {
+        domain: String,
+        priority: u16,
+        host: String,
+        class: QClass,
+        ttl: u32,
+    },
+    SOA {
+        domain: String,
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+        class: QClass,
+        ttl: u32,
+    },
+    TXT {
+        domain: String,
+        /// The character-strings, each at most 255 bytes
+        data: Vec<Vec<u8>>,
+        class: QClass,
+        ttl: u32,
+    },
+    SRV {
+        domain: String,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+        class: QClass,
         ttl: u32,
     },
 }
 
 impl Record {
-    pub fn read(buffer: &mut BytePacketBuffer) -> anyhow::Result<Record> {
+    pub fn read(buffer: &mut BytePacketBuffer) -> Result<Record, ParseError> {
         let mut domain = String::new();
         buffer.read_qname(&mut domain)?;
 
         let qtype_num = buffer.read_u16()?;
-        let qtype: QType = qtype_num.into();
-        let _ = buffer.read_u16()?;
+        let class: QClass = buffer.read_u16()?.into();
         let ttl = buffer.read_u32()?;
         let data_len = buffer.read_u16()?;
+        let start = buffer.pos();
 
-        match qtype {
+        let record = match QType::from(qtype_num) {
             QType::A => {
+                if data_len != 4 {
+                    return Err(ParseError::InvalidRdata {
+                        offset: buffer.pos(),
+                        qtype: qtype_num,
+                        reason: "an IPv4 address must be 4 bytes",
+                    });
+                }
                 let raw_addr = buffer.read_u32()?;
                 let addr = Ipv4Addr::new(
                     ((raw_addr >> 24) & 0xFF) as u8,
@@ -58,33 +131,202 @@ impl Record {
                     (raw_addr & 0xFF) as u8,
                 );
 
-                Ok(Record::A { domain, addr, ttl })
+                Ok(Record::A {
+                    domain,
+                    addr,
+                    class,
+                    ttl,
+                })
+            }
+            QType::AAAA => {
+                if data_len != 16 {
+                    return Err(ParseError::InvalidRdata {
+                        offset: buffer.pos(),
+                        qtype: qtype_num,
+                        reason: "an IPv6 address must be 16 bytes",
+                    });
+                }
+                let raw_addr1 = buffer.read_u32()?;
+                let raw_addr2 = buffer.read_u32()?;
+                let raw_addr3 = buffer.read_u32()?;
+                let raw_addr4 = buffer.read_u32()?;
+                let addr = Ipv6Addr::new(
+                    ((raw_addr1 >> 16) & 0xFFFF) as u16,
+                    (raw_addr1 & 0xFFFF) as u16,
+                    ((raw_addr2 >> 16) & 0xFFFF) as u16,
+                    (raw_addr2 & 0xFFFF) as u16,
+                    ((raw_addr3 >> 16) & 0xFFFF) as u16,
+                    (raw_addr3 & 0xFFFF) as u16,
+                    ((raw_addr4 >> 16) & 0xFFFF) as u16,
+                    (raw_addr4 & 0xFFFF) as u16,
+                );
+
+                Ok(Record::AAAA {
+                    domain,
+                    addr,
+                    class,
+                    ttl,
+                })
+            }
+            QType::NS => {
+                let mut host = String::new();
+                buffer.read_qname(&mut host)?;
+
+                Ok(Record::NS {
+                    domain,
+                    host,
+                    class,
+                    ttl,
+                })
+            }
+            QType::CNAME => {
+                let mut host = String::new();
+                buffer.read_qname(&mut host)?;
+
+                Ok(Record::CNAME {
+                    domain,
+                    host,
+                    class,
+                    ttl,
+                })
+            }
+            QType::PTR => {
+                let mut host = String::new();
+                buffer.read_qname(&mut host)?;
+
+                Ok(Record::PTR {
+                    domain,
+                    host,
+                    class,
+                    ttl,
+                })
+            }
+            QType::MX => {
+                let priority = buffer.read_u16()?;
+                let mut host = String::new();
+                buffer.read_qname(&mut host)?;
+
+                Ok(Record::MX {
+                    domain,
+                    priority,
+                    host,
+                    class,
+                    ttl,
+                })
+            }
+            QType::SOA => {
+                let mut mname = String::new();
+                buffer.read_qname(&mut mname)?;
+                let mut rname = String::new();
+                buffer.read_qname(&mut rname)?;
+                let serial = buffer.read_u32()?;
+                let refresh = buffer.read_u32()?;
+                let retry = buffer.read_u32()?;
+                let expire = buffer.read_u32()?;
+                let minimum = buffer.read_u32()?;
+
+                Ok(Record::SOA {
+                    domain,
+                    mname,
+                    rname,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                    class,
+                    ttl,
+                })
+            }
+            QType::TXT => {
+                if data_len == 0 {
+                    return Err(ParseError::InvalidRdata {
+                        offset: buffer.pos(),
+                        qtype: qtype_num,
+                        reason: "TXT needs at least one character-string",
+                    });
+                }
+                let end = buffer.pos() + data_len as usize;
+                let mut data = Vec::new();
+                while buffer.pos() < end {
+                    let len = buffer.read()? as usize;
+                    let pos = buffer.pos();
+                    if pos + len > end {
+                        return Err(ParseError::InvalidRdata {
+                            offset: pos - 1,
+                            qtype: qtype_num,
+                            reason: "character-string overruns the rdata",
+                        });
+                    }
+                    data.push(buffer.get_range(pos, len)?.to_vec());
+                    buffer.step(len)?;
+                }
+
+                Ok(Record::TXT {
+                    domain,
+                    data,
+                    class,
+                    ttl,
+                })
+            }
+            QType::SRV => {
+                let priority = buffer.read_u16()?;
+                let weight = buffer.read_u16()?;
+                let port = buffer.read_u16()?;
+                let mut target = String::new();
+                buffer.read_qname(&mut target)?;
+
+                Ok(Record::SRV {
+                    domain,
+                    priority,
+                    weight,
+                    port,
+                    target,
+                    class,
+                    ttl,
+                })
             }
             _ => {
+                let data = buffer.get_range(buffer.pos(), data_len as usize)?.to_vec();
                 buffer.step(data_len as usize)?;
 
                 Ok(Record::Unknown {
                     domain,
                     qtype: qtype_num,
-                    data_len,
+                    class,
+                    data,
                     ttl,
                 })
             }
+        }?;
+
+        // The fields of types with names in them don't say how long they
+        // are, so a short or padded RDATA would leave us out of step with
+        // the records after it.
+        if buffer.pos() != start + data_len as usize {
+            return Err(ParseError::InvalidRdata {
+                offset: start,
+                qtype: qtype_num,
+                reason: "rdata length doesn't match its contents",
+            });
         }
+
+        Ok(record)
     }
 
-    pub fn write(&self, buffer: &mut BytePacketBuffer) -> anyhow::Result<usize> {
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<usize, WriteError> {
         let start_pos = buffer.pos();
 
         match *self {
             Record::A {
                 ref domain,
                 ref addr,
+                class,
                 ttl,
             } => {
                 buffer.write_qname(domain)?;
-                buffer.write_u16(QType::A as u16)?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(QType::A.to_num())?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
                 buffer.write_u16(4)?;
 
@@ -94,11 +336,653 @@ impl Record {
                 buffer.write_u8(octets[2])?;
                 buffer.write_u8(octets[3])?;
             }
-            _ => {
-                println!("Skipping record: {:?}", self);
+            Record::AAAA {
+                ref domain,
+                ref addr,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::AAAA.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(16)?;
+
+                for segment in &addr.segments() {
+                    buffer.write_u16(*segment)?;
+                }
+            }
+            Record::NS {
+                ref domain,
+                ref host,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::NS.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                // The size of a compressed name isn't known until it has
+                // been written, so RDLENGTH is filled in afterwards.
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::CNAME {
+                ref domain,
+                ref host,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::CNAME.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::PTR {
+                ref domain,
+                ref host,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::PTR.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::MX {
+                ref domain,
+                priority,
+                ref host,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::MX.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(priority)?;
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::SOA {
+                ref domain,
+                ref mname,
+                ref rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::SOA.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(mname)?;
+                buffer.write_qname(rname)?;
+                buffer.write_u32(serial)?;
+                buffer.write_u32(refresh)?;
+                buffer.write_u32(retry)?;
+                buffer.write_u32(expire)?;
+                buffer.write_u32(minimum)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::TXT {
+                ref domain,
+                ref data,
+                class,
+                ttl,
+            } => {
+                if data.is_empty() {
+                    return Err(WriteError::EmptyTxt);
+                }
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::TXT.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                for string in data {
+                    if string.len() > 255 {
+                        return Err(WriteError::CharacterStringTooLong { len: string.len() });
+                    }
+                    buffer.write_u8(string.len() as u8)?;
+                    for b in string {
+                        buffer.write_u8(*b)?;
+                    }
+                }
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::SRV {
+                ref domain,
+                priority,
+                weight,
+                port,
+                ref target,
+                class,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QType::SRV.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(priority)?;
+                buffer.write_u16(weight)?;
+                buffer.write_u16(port)?;
+                // RFC 2782: the target name must not be compressed
+                buffer.write_qname_uncompressed(target)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            Record::Unknown {
+                ref domain,
+                qtype,
+                class,
+                ref data,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(qtype)?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(data.len() as u16)?;
+
+                for b in data {
+                    buffer.write_u8(*b)?;
+                }
             }
         }
 
         Ok(buffer.pos() - start_pos)
     }
+
+    /// The owner name of the record
+    pub fn domain(&self) -> &str {
+        match self {
+            Record::Unknown { domain, .. }
+            | Record::A { domain, .. }
+            | Record::AAAA { domain, .. }
+            | Record::NS { domain, .. }
+            | Record::CNAME { domain, .. }
+            | Record::PTR { domain, .. }
+            | Record::MX { domain, .. }
+            | Record::SOA { domain, .. }
+            | Record::TXT { domain, .. }
+            | Record::SRV { domain, .. } => domain,
+        }
+    }
+
+    pub fn qtype(&self) -> QType {
+        match *self {
+            Record::Unknown { qtype, .. } => qtype.into(),
+            Record::A { .. } => QType::A,
+            Record::AAAA { .. } => QType::AAAA,
+            Record::NS { .. } => QType::NS,
+            Record::CNAME { .. } => QType::CNAME,
+            Record::PTR { .. } => QType::PTR,
+            Record::MX { .. } => QType::MX,
+            Record::SOA { .. } => QType::SOA,
+            Record::TXT { .. } => QType::TXT,
+            Record::SRV { .. } => QType::SRV,
+        }
+    }
+
+    pub fn ttl(&self) -> u32 {
+        match *self {
+            Record::Unknown { ttl, .. }
+            | Record::A { ttl, .. }
+            | Record::AAAA { ttl, .. }
+            | Record::NS { ttl, .. }
+            | Record::CNAME { ttl, .. }
+            | Record::PTR { ttl, .. }
+            | Record::MX { ttl, .. }
+            | Record::SOA { ttl, .. }
+            | Record::TXT { ttl, .. }
+            | Record::SRV { ttl, .. } => ttl,
+        }
+    }
+
+    pub fn set_ttl(&mut self, new_ttl: u32) {
+        match self {
+            Record::Unknown { ttl, .. }
+            | Record::A { ttl, .. }
+            | Record::AAAA { ttl, .. }
+            | Record::NS { ttl, .. }
+            | Record::CNAME { ttl, .. }
+            | Record::PTR { ttl, .. }
+            | Record::MX { ttl, .. }
+            | Record::SOA { ttl, .. }
+            | Record::TXT { ttl, .. }
+            | Record::SRV { ttl, .. } => *ttl = new_ttl,
+        }
+    }
+
+    /// Just the RDATA of the record in presentation format, as printed by
+    /// `dig +short`
+    pub fn rdata_to_string(&self) -> String {
+        match self {
+            Record::Unknown { data, .. } => format_generic_rdata(data),
+            Record::A { addr, .. } => addr.to_string(),
+            Record::AAAA { addr, .. } => addr.to_string(),
+            Record::NS { host, .. } | Record::CNAME { host, .. } | Record::PTR { host, .. } => {
+                to_presentation(host)
+            }
+            Record::MX { priority, host, .. } => {
+                format!("{} {}", priority, to_presentation(host))
+            }
+            Record::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+                ..
+            } => format!(
+                "{} {} {} {} {} {} {}",
+                to_presentation(mname),
+                to_presentation(rname),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum
+            ),
+            Record::TXT { data, .. } => {
+                let strings: Vec<String> = data.iter().map(|s| quote(s)).collect();
+                strings.join(" ")
+            }
+            Record::SRV {
+                priority,
+                weight,
+                port,
+                target,
+                ..
+            } => format!(
+                "{} {} {} {}",
+                priority,
+                weight,
+                port,
+                to_presentation(target)
+            ),
+        }
+    }
+
+    /// The class every variant carries, usually `QClass::IN`
+    pub fn class(&self) -> QClass {
+        match *self {
+            Record::Unknown { class, .. }
+            | Record::A { class, .. }
+            | Record::AAAA { class, .. }
+            | Record::NS { class, .. }
+            | Record::CNAME { class, .. }
+            | Record::PTR { class, .. }
+            | Record::MX { class, .. }
+            | Record::SOA { class, .. }
+            | Record::TXT { class, .. }
+            | Record::SRV { class, .. } => class,
+        }
+    }
+
+    /// The character-strings of a TXT record concatenated with nothing in
+    /// between, which is how SPF (RFC 7208 §3.3) and DKIM (RFC 6376 §3.6.2.2)
+    /// reassemble values too long for a single string. `None` for any other
+    /// type of record.
+    pub fn txt_joined(&self) -> Option<String> {
+        match self {
+            Record::TXT { data, .. } => Some(String::from_utf8_lossy(&data.concat()).into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Split a long value into 255 byte character-strings for a TXT record,
+    /// the inverse of `txt_joined`. An empty value is a single empty string,
+    /// since a TXT record needs at least one.
+    pub fn txt_split(value: &str) -> Vec<Vec<u8>> {
+        if value.is_empty() {
+            return vec![Vec::new()];
+        }
+        value.as_bytes().chunks(255).map(|c| c.to_vec()).collect()
+    }
+}
+
+impl fmt::Display for Record {
+    /// The record in the presentation format of RFC 1035 §5.1, as a line of
+    /// a zone file: `name TTL CLASS TYPE RDATA`, with absolute names
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            to_presentation(self.domain()),
+            self.ttl(),
+            self.class(),
+            self.qtype(),
+            self.rdata_to_string()
+        )
+    }
+}
+
+/// A character-string in double quotes, with the quote, the backslash and
+/// anything unprintable escaped
+fn quote(string: &[u8]) -> String {
+    let mut out = String::with_capacity(string.len() + 2);
+    out.push('"');
+    for &b in string {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7E => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Render RDATA in the generic `\# <length> <hex>` presentation format of
+/// RFC 3597 §5, which can express the RDATA of any type.
+pub fn format_generic_rdata(data: &[u8]) -> String {
+    let mut out = format!("\\# {}", data.len());
+    if !data.is_empty() {
+        out.push(' ');
+        for b in data {
+            out.push_str(&format!("{:02x}", b));
+        }
+    }
+    out
+}
+
+/// Parse RDATA written in the generic `\# <length> <hex>` presentation
+/// format. The hex may be split into groups by whitespace. Returns `None` if
+/// the text isn't in that format or the length doesn't match the data.
+pub fn parse_generic_rdata(text: &str) -> Option<Vec<u8>> {
+    let mut parts = text.split_whitespace();
+    if parts.next()? != "\\#" {
+        return None;
+    }
+    let len: usize = parts.next()?.parse().ok()?;
+
+    // An odd number of digits leaves a final `get` out of range.
+    let hex: String = parts.collect();
+    let data = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    if data.len() != len {
+        return None;
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_generic_rdata() {
+        assert_eq!(format_generic_rdata(&[]), "\\# 0");
+        assert_eq!(
+            format_generic_rdata(&[0x0a, 0x00, 0x00, 0x01]),
+            "\\# 4 0a000001"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let records = [
+            (
+                Record::A {
+                    domain: "www.example.com".to_string(),
+                    addr: Ipv4Addr::new(192, 0, 2, 1),
+                    class: QClass::IN,
+                    ttl: 300,
+                },
+                "www.example.com. 300 IN A 192.0.2.1",
+            ),
+            (
+                Record::MX {
+                    domain: "".to_string(),
+                    priority: 10,
+                    host: "mail.example.com".to_string(),
+                    class: QClass::IN,
+                    ttl: 60,
+                },
+                ". 60 IN MX 10 mail.example.com.",
+            ),
+            (
+                Record::TXT {
+                    domain: "example.com".to_string(),
+                    data: vec![b"v=spf1 -all".to_vec(), b"say \"hi\"\n".to_vec()],
+                    class: QClass::CH,
+                    ttl: 0,
+                },
+                "example.com. 0 CH TXT \"v=spf1 -all\" \"say \\\"hi\\\"\\010\"",
+            ),
+            (
+                Record::SRV {
+                    domain: "_sip._tcp.example.com".to_string(),
+                    priority: 10,
+                    weight: 60,
+                    port: 5060,
+                    target: "sip.example.com".to_string(),
+                    class: QClass::IN,
+                    ttl: 60,
+                },
+                "_sip._tcp.example.com. 60 IN SRV 10 60 5060 sip.example.com.",
+            ),
+            (
+                Record::Unknown {
+                    domain: "example.com".to_string(),
+                    qtype: 65534,
+                    class: QClass::Unknown(42),
+                    data: vec![0x0a, 0x00],
+                    ttl: 60,
+                },
+                "example.com. 60 CLASS42 TYPE65534 \\# 2 0a00",
+            ),
+        ];
+        for (record, text) in records {
+            assert_eq!(record.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_parse_generic_rdata() {
+        assert_eq!(parse_generic_rdata("\\# 0"), Some(vec![]));
+        assert_eq!(
+            parse_generic_rdata("\\# 4 0A000001"),
+            Some(vec![0x0a, 0x00, 0x00, 0x01])
+        );
+        assert_eq!(
+            parse_generic_rdata("\\# 4 0a00 00 01"),
+            Some(vec![0x0a, 0x00, 0x00, 0x01])
+        );
+        assert_eq!(parse_generic_rdata("\\# 3 0a000001"), None);
+        assert_eq!(parse_generic_rdata("\\# 2 0a0"), None);
+        assert_eq!(parse_generic_rdata("\\# 1 zz"), None);
+        assert_eq!(parse_generic_rdata("10.0.0.1"), None);
+    }
+
+    #[test]
+    fn test_chaos_class_round_trip() {
+        let record = Record::TXT {
+            domain: "version.bind".to_string(),
+            data: vec![b"9.18.0".to_vec()],
+            class: QClass::CH,
+            ttl: 0,
+        };
+        assert_eq!(record.class(), QClass::CH);
+
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        assert_eq!(buffer.filled()[16..18], [0, 3]);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(Record::read(&mut buffer).unwrap(), record);
+    }
+
+    #[test]
+    fn test_srv_target_is_not_compressed() {
+        let record = Record::SRV {
+            domain: "_grpc._tcp.codecrafters.io".to_string(),
+            priority: 10,
+            weight: 60,
+            port: 8443,
+            target: "api.codecrafters.io".to_string(),
+            class: QClass::IN,
+            ttl: 300,
+        };
+
+        let mut buffer = BytePacketBuffer::new();
+        let size = record.write(&mut buffer).unwrap();
+        // 28 byte owner name, 10 bytes of fixed fields, 6 bytes of priority,
+        // weight and port and the full 21 byte target
+        assert_eq!(size, 28 + 10 + 6 + 21);
+        assert_eq!(buffer.filled()[36..38], [0, 27]);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(Record::read(&mut buffer).unwrap(), record);
+    }
+
+    #[test]
+    fn test_unknown_record_round_trip() {
+        let record = Record::Unknown {
+            domain: "codecrafters.io".to_string(),
+            qtype: 65280,
+            class: QClass::IN,
+            data: vec![0xC0, 0x0C, 0xde, 0xad],
+            ttl: 60,
+        };
+
+        let mut buffer = BytePacketBuffer::new();
+        let size = record.write(&mut buffer).unwrap();
+        assert_eq!(size, 17 + 10 + 4);
+        assert_eq!(buffer.filled()[27..], [0xC0, 0x0C, 0xde, 0xad]);
+
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        assert_eq!(Record::read(&mut buffer).unwrap(), record);
+    }
+
+    #[test]
+    fn test_rdata_length_mismatch() {
+        let record = Record::MX {
+            domain: "codecrafters.io".to_string(),
+            priority: 10,
+            host: "mail.codecrafters.io".to_string(),
+            class: QClass::IN,
+            ttl: 300,
+        };
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        let bytes = buffer.filled().to_vec();
+        // 17 byte owner name, then type, class and TTL before RDLENGTH. The
+        // host is "mail" and a pointer to the owner.
+        assert_eq!(bytes[25..27], [0, 9]);
+
+        for (data_len, padding) in [(8, 0), (10, 1)] {
+            let mut bytes = bytes.clone();
+            bytes[25..27].copy_from_slice(&u16::to_be_bytes(data_len));
+            bytes.resize(bytes.len() + padding, 0);
+
+            let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+            match Record::read(&mut buffer) {
+                Err(ParseError::InvalidRdata { offset, qtype, .. }) => {
+                    assert_eq!((offset, qtype), (27, 15))
+                }
+                other => panic!("Expected invalid rdata, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_txt() {
+        let record = Record::TXT {
+            domain: "codecrafters.io".to_string(),
+            data: Record::txt_split(""),
+            class: QClass::IN,
+            ttl: 60,
+        };
+        assert_eq!(record.txt_joined().unwrap(), "");
+
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.filled());
+        let read = Record::read(&mut buffer).unwrap();
+        assert_eq!(read, record);
+
+        let text = read.to_string();
+        assert_eq!(text, "codecrafters.io. 60 IN TXT \"\"");
+        assert_eq!(crate::zone_file::parse(&text, "").unwrap(), vec![record]);
+
+        // No character-strings at all isn't a TXT record
+        let empty = Record::TXT {
+            domain: "codecrafters.io".to_string(),
+            data: Vec::new(),
+            class: QClass::IN,
+            ttl: 60,
+        };
+        let mut buffer = BytePacketBuffer::new();
+        assert_eq!(empty.write(&mut buffer), Err(WriteError::EmptyTxt));
+
+        // codecrafters.io TXT with a TTL of 60 and RDLENGTH 0
+        let bytes = b"\x0ccodecrafters\x02io\x00\x00\x10\x00\x01\x00\x00\x00\x3c\x00\x00";
+        let mut buffer = BytePacketBuffer::from_bytes(bytes);
+        assert!(matches!(
+            Record::read(&mut buffer),
+            Err(ParseError::InvalidRdata {
+                offset: 27,
+                qtype: 16,
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,685 @@
+// An iterative resolver (RFC 1034 §5.3.3): starting from the root servers,
+// ask each nameserver without recursion and follow its referrals down the
+// tree until one of them answers authoritatively.
+use crate::cache::{Cache, Cached};
+use crate::client::Client;
+use crate::error::{ClientError, ResolveError};
+use crate::header::{Header, RCode};
+use crate::name::{is_subdomain, normalize};
+use crate::packet::Packet;
+use crate::question::{QClass, QType, Question};
+use crate::random::random_u64;
+use crate::record::Record;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// The root servers, from https://www.iana.org/domains/root/servers
+pub const ROOT_HINTS: [(&str, Ipv4Addr); 13] = [
+    ("a.root-servers.net", Ipv4Addr::new(198, 41, 0, 4)),
+    ("b.root-servers.net", Ipv4Addr::new(170, 247, 170, 2)),
+    ("c.root-servers.net", Ipv4Addr::new(192, 33, 4, 12)),
+    ("d.root-servers.net", Ipv4Addr::new(199, 7, 91, 13)),
+    ("e.root-servers.net", Ipv4Addr::new(192, 203, 230, 10)),
+    ("f.root-servers.net", Ipv4Addr::new(192, 5, 5, 241)),
+    ("g.root-servers.net", Ipv4Addr::new(192, 112, 36, 4)),
+    ("h.root-servers.net", Ipv4Addr::new(198, 97, 190, 53)),
+    ("i.root-servers.net", Ipv4Addr::new(192, 36, 148, 17)),
+    ("j.root-servers.net", Ipv4Addr::new(192, 58, 128, 30)),
+    ("k.root-servers.net", Ipv4Addr::new(193, 0, 14, 129)),
+    ("l.root-servers.net", Ipv4Addr::new(199, 7, 83, 42)),
+    ("m.root-servers.net", Ipv4Addr::new(202, 12, 27, 33)),
+];
+
+/// How many record sets the cache of a `Resolver::default()` holds
+pub const DEFAULT_CACHE_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    /// The servers to start every resolution from
+    pub roots: Vec<IpAddr>,
+    /// The port every nameserver is asked on. Only tests need anything but
+    /// 53, since referrals carry addresses but no ports.
+    pub port: u16,
+    /// How long to wait for each response
+    pub timeout: Duration,
+    /// How many times to resend a query to the same server
+    pub retries: usize,
+    /// How many queries a single resolution may send in total, including
+    /// those for the addresses of nameservers and for CNAME targets
+    pub max_queries: usize,
+    /// How many CNAMEs to follow from the name asked for
+    pub max_cname_chain: usize,
+    /// How deeply resolving the address of a nameserver may in turn need
+    /// the address of another nameserver
+    pub max_depth: usize,
+    /// Where answers are kept until they expire, `None` to always ask. The
+    /// cache may be shared with other resolvers.
+    pub cache: Option<Arc<Mutex<Cache>>>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver {
+            roots: ROOT_HINTS
+                .iter()
+                .map(|(_, addr)| IpAddr::V4(*addr))
+                .collect(),
+            port: 53,
+            timeout: Duration::from_secs(2),
+            retries: 0,
+            max_queries: 64,
+            max_cname_chain: 8,
+            max_depth: 4,
+            cache: Some(Arc::new(Mutex::new(Cache::new(DEFAULT_CACHE_SIZE)))),
+        }
+    }
+}
+
+/// The work done on one resolution so far, shared by the lookups of
+/// nameserver addresses it needs along the way.
+struct Budget {
+    queries: usize,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    /// Find the records of `qtype` for `name`. The response has the CNAMEs
+    /// that led to them first in its answer section. Negative answers are
+    /// responses too, with `NameError` or no answers, and the SOA of the
+    /// zone in the authority section.
+    pub fn resolve(&self, name: &str, qtype: QType) -> Result<Packet, ResolveError> {
+        let mut budget = Budget { queries: 0 };
+        self.resolve_with(name, qtype, &mut budget, 0)
+    }
+
+    fn resolve_with(
+        &self,
+        name: &str,
+        qtype: QType,
+        budget: &mut Budget,
+        depth: usize,
+    ) -> Result<Packet, ResolveError> {
+        let mut result = Packet {
+            header: Header {
+                is_reply: true,
+                recursion_available: true,
+                ..Header::default()
+            },
+            questions: vec![Question {
+                name: normalize(name),
+                qtype,
+                qclass: QClass::IN,
+            }],
+            ..Packet::default()
+        };
+
+        let mut current = normalize(name);
+        let mut cnames = 0;
+        loop {
+            match self.cached(&current, qtype) {
+                Some(Cached::Records(records)) => {
+                    result.answers.extend(records);
+                    return Ok(result);
+                }
+                Some(Cached::NoData { soa }) => {
+                    result.authorities.push(soa);
+                    return Ok(result);
+                }
+                Some(Cached::NxDomain { soa }) => {
+                    result.header.rcode = RCode::NameError;
+                    result.authorities.push(soa);
+                    return Ok(result);
+                }
+                None => {}
+            }
+            if qtype != QType::CNAME {
+                if let Some(Cached::Records(mut records)) = self.cached(&current, QType::CNAME) {
+                    if let Some(Record::CNAME { host, .. }) = records.first() {
+                        self.count_cname(&mut cnames)?;
+                        current = normalize(host);
+                        result.answers.push(records.remove(0));
+                        continue;
+                    }
+                }
+            }
+
+            let (response, zone) = self.lookup(&current, qtype, budget, depth)?;
+            result.header.rcode = response.header.rcode;
+            let asked = current.clone();
+            let learned = result.answers.len();
+
+            // Follow the chain through the answer section, taking only
+            // records owned by the names on it, and only while those are in
+            // the zone of the server that answered: it has no say over any
+            // other names, and believing it would let it poison the cache.
+            while is_subdomain(&current, &zone) {
+                let owned = |record: &&Record| record.domain().eq_ignore_ascii_case(&current);
+                let matching: Vec<Record> = response
+                    .answers
+                    .iter()
+                    .filter(owned)
+                    .filter(|record| qtype == QType::ANY || record.qtype() == qtype)
+                    .cloned()
+                    .collect();
+                if !matching.is_empty() {
+                    result.answers.extend(matching);
+                    self.remember(&result.answers[learned..]);
+                    return Ok(result);
+                }
+
+                let cname = response
+                    .answers
+                    .iter()
+                    .filter(owned)
+                    .find_map(|record| match record {
+                        Record::CNAME { host, .. } => Some((record.clone(), normalize(host))),
+                        _ => None,
+                    });
+                match cname {
+                    Some((record, host)) => {
+                        self.count_cname(&mut cnames)?;
+                        result.answers.push(record);
+                        current = host;
+                    }
+                    None => break,
+                }
+            }
+
+            self.remember(&result.answers[learned..]);
+
+            // Either the chain leads out of what this server knows, and we
+            // have to ask about its end from the root, or the name we asked
+            // about doesn't have the records at all. The response code only
+            // counts for names in the server's zone.
+            if !is_subdomain(&current, &zone) {
+                continue;
+            }
+            if current == asked || response.header.rcode != RCode::NoError {
+                self.remember_negative(&current, &zone, qtype, &response);
+                result.authorities = response.authorities;
+                return Ok(result);
+            }
+        }
+    }
+
+    fn count_cname(&self, cnames: &mut usize) -> Result<(), ResolveError> {
+        *cnames += 1;
+        if *cnames > self.max_cname_chain {
+            return Err(ResolveError::CnameChainTooLong {
+                limit: self.max_cname_chain,
+            });
+        }
+        Ok(())
+    }
+
+    fn cached(&self, name: &str, qtype: QType) -> Option<Cached> {
+        let cache = self.cache.as_ref()?;
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.lookup(name, qtype, QClass::IN)
+    }
+
+    fn remember(&self, records: &[Record]) {
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            cache.insert(records);
+        }
+    }
+
+    /// Remember that `name` doesn't exist, or doesn't have records of
+    /// `qtype`, if the negative `response` from a server for `zone` has the
+    /// SOA of a zone between the two to say for how long (RFC 2308 §5).
+    fn remember_negative(&self, name: &str, zone: &str, qtype: QType, response: &Packet) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        let soa = response.authorities.iter().find(|record| {
+            let owner = normalize(record.domain());
+            record.qtype() == QType::SOA && is_subdomain(name, &owner) && is_subdomain(&owner, zone)
+        });
+        let Some(soa) = soa else {
+            return;
+        };
+
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if response.header.rcode == RCode::NameError {
+            cache.insert_nxdomain(name, QClass::IN, soa);
+        } else {
+            cache.insert_nodata(name, qtype, QClass::IN, soa);
+        }
+    }
+
+    /// Walk down from the roots to a server that answers for `name` itself,
+    /// and return its response along with the zone it was asked as a server
+    /// for.
+    fn lookup(
+        &self,
+        name: &str,
+        qtype: QType,
+        budget: &mut Budget,
+        depth: usize,
+    ) -> Result<(Packet, String), ResolveError> {
+        let mut zone = String::new();
+        let mut servers = self.roots.clone();
+        // Spread the load over the roots.
+        let offset = random_u64() as usize % servers.len().max(1);
+        servers.rotate_left(offset);
+
+        'referral: loop {
+            for server in servers {
+                if budget.queries >= self.max_queries {
+                    return Err(ResolveError::TooManyQueries {
+                        limit: self.max_queries,
+                    });
+                }
+                budget.queries += 1;
+
+                let response = match self.query(server, name, qtype) {
+                    Ok(response) => response,
+                    Err(_) => continue,
+                };
+
+                match response.header.rcode {
+                    RCode::NoError | RCode::NameError => {}
+                    // A server which fails or refuses isn't serving the zone
+                    // properly; maybe another one is.
+                    _ => continue,
+                }
+                // An authoritative response is the final word, even when it
+                // has the zone's own NS records in its authority section.
+                if response.header.authoritative
+                    || response.header.rcode == RCode::NameError
+                    || !response.answers.is_empty()
+                {
+                    return Ok((response, zone));
+                }
+
+                let referral: Vec<(String, String)> = response
+                    .authorities
+                    .iter()
+                    .filter_map(|record| match record {
+                        Record::NS { domain, host, .. } => {
+                            Some((normalize(domain), normalize(host)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                let Some((cut, _)) = referral.first() else {
+                    // No answers and no referral: the name exists, but not
+                    // with this type.
+                    return Ok((response, zone));
+                };
+
+                // A referral has to lead closer to the name, or we could go
+                // round in circles.
+                let cut = cut.clone();
+                if cut == zone || !is_subdomain(&cut, &zone) || !is_subdomain(name, &cut) {
+                    continue;
+                }
+
+                let hosts: Vec<String> = referral
+                    .into_iter()
+                    .filter(|(domain, _)| *domain == cut)
+                    .map(|(_, host)| host)
+                    .collect();
+                let addresses =
+                    self.nameserver_addresses(&zone, &hosts, &response, budget, depth)?;
+                if addresses.is_empty() {
+                    continue;
+                }
+
+                zone = cut;
+                servers = addresses;
+                continue 'referral;
+            }
+
+            return Err(ResolveError::NoUsableServers { zone });
+        }
+    }
+
+    /// The addresses of the nameservers `hosts` a server for `zone` referred
+    /// us to: from the glue in its additional section, or else by resolving
+    /// them ourselves.
+    fn nameserver_addresses(
+        &self,
+        zone: &str,
+        hosts: &[String],
+        response: &Packet,
+        budget: &mut Budget,
+        depth: usize,
+    ) -> Result<Vec<IpAddr>, ResolveError> {
+        // Only glue within the zone of the server that sent it can be
+        // trusted; anything else may be an attempt to poison us.
+        let mut addresses: Vec<IpAddr> = response
+            .resources
+            .iter()
+            .filter(|record| {
+                let owner = normalize(record.domain());
+                hosts.contains(&owner) && is_subdomain(&owner, zone)
+            })
+            .filter_map(|record| match record {
+                Record::A { addr, .. } => Some(IpAddr::V4(*addr)),
+                Record::AAAA { addr, .. } => Some(IpAddr::V6(*addr)),
+                _ => None,
+            })
+            .collect();
+        // IPv4 first, as IPv6 is less often reachable.
+        addresses.sort_by_key(|addr| addr.is_ipv6());
+        addresses.dedup();
+        if !addresses.is_empty() || depth >= self.max_depth {
+            return Ok(addresses);
+        }
+
+        for host in hosts {
+            let resolved = match self.resolve_with(host, QType::A, budget, depth + 1) {
+                Ok(resolved) => resolved,
+                Err(ResolveError::TooManyQueries { limit }) => {
+                    return Err(ResolveError::TooManyQueries { limit })
+                }
+                Err(_) => continue,
+            };
+            addresses.extend(resolved.answers.iter().filter_map(|record| match record {
+                Record::A { addr, .. } => Some(IpAddr::V4(*addr)),
+                _ => None,
+            }));
+            if !addresses.is_empty() {
+                break;
+            }
+        }
+        Ok(addresses)
+    }
+
+    fn query(&self, server: IpAddr, name: &str, qtype: QType) -> Result<Packet, ClientError> {
+        let client = Client {
+            timeout: self.timeout,
+            retries: self.retries,
+            ..Client::new(SocketAddr::new(server, self.port))
+        };
+        let mut request = Client::build_query(name, qtype);
+        request.header.recursion_desired = false;
+        client.query(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authority::{Authority, Zone};
+    use crate::server::{self, response_to, RequestHandler};
+    use crate::zone_file;
+
+    const ROOT: &str = "
+$TTL 3600
+@ SOA a.root-servers.test. hostmaster 1 2 3 4 60
+  NS a.root-servers.test.
+a.root-servers.test. A 127.0.0.10
+com NS ns.com.
+ns.com. A 127.0.0.11
+; No glue: the resolver has to look up ns.org-servers.com itself
+org NS ns.org-servers.com.
+; Nobody answers for this one
+lame NS ns.lame.
+ns.lame. A 127.0.0.19
+";
+
+    const COM: &str = "
+$ORIGIN com.
+$TTL 3600
+@ SOA ns hostmaster 1 2 3 4 60
+  NS ns
+ns A 127.0.0.11
+ns.org-servers A 127.0.0.13
+example NS ns.example
+ns.example A 127.0.0.12
+evil NS ns.evil
+ns.evil A 127.0.0.14
+";
+
+    const EXAMPLE_COM: &str = "
+$ORIGIN example.com.
+$TTL 300
+@ SOA ns hostmaster 1 2 3 4 60
+  NS ns
+ns A 127.0.0.12
+www A 192.0.2.1
+alias CNAME www
+elsewhere CNAME www.example.org.
+loop1 CNAME loop2
+loop2 CNAME loop1
+";
+
+    const ORG: &str = "
+$ORIGIN org.
+$TTL 300
+@ SOA ns.org-servers.com. hostmaster 1 2 3 4 60
+  NS ns.org-servers.com.
+www.example A 192.0.2.2
+";
+
+    /// The server for evil.com, which slips an address for a name outside
+    /// its zone into its answers, and answers NODATA with its NS records
+    /// rather than its SOA
+    struct Evil;
+
+    impl RequestHandler for Evil {
+        fn handle(&self, request: &Packet) -> Packet {
+            let mut response = response_to(request);
+            response.header.authoritative = true;
+            let question = &request.questions[0];
+            if question.qtype == QType::A {
+                response.answers.push(Record::CNAME {
+                    domain: question.name.clone(),
+                    host: "www.example.org".to_string(),
+                    class: QClass::IN,
+                    ttl: 300,
+                });
+                response.answers.push(Record::A {
+                    domain: "www.example.org".to_string(),
+                    addr: Ipv4Addr::new(198, 51, 100, 66),
+                    class: QClass::IN,
+                    ttl: 300,
+                });
+            } else {
+                response.authorities.push(Record::NS {
+                    domain: "evil.com".to_string(),
+                    host: "ns.evil.com".to_string(),
+                    class: QClass::IN,
+                    ttl: 300,
+                });
+            }
+            response
+        }
+    }
+
+    fn authority(origin: &str, text: &str) -> Arc<Authority> {
+        let mut zone = Zone::new(origin);
+        for record in zone_file::parse(text, origin).unwrap() {
+            zone.add(record).unwrap();
+        }
+        let mut authority = Authority::new();
+        authority.add_zone(zone);
+        Arc::new(authority)
+    }
+
+    /// Stand-in servers for a small tree on loopback addresses sharing one
+    /// port, and a resolver that starts from its root
+    fn resolver() -> Resolver {
+        let root = server::spawn("127.0.0.10:0".parse().unwrap(), authority("", ROOT)).unwrap();
+        let port = root.port();
+        for (ip, origin, text) in [
+            ("127.0.0.11", "com", COM),
+            ("127.0.0.12", "example.com", EXAMPLE_COM),
+            ("127.0.0.13", "org", ORG),
+        ] {
+            server::spawn(
+                SocketAddr::new(ip.parse().unwrap(), port),
+                authority(origin, text),
+            )
+            .unwrap();
+        }
+        server::spawn(
+            SocketAddr::new("127.0.0.14".parse().unwrap(), port),
+            Arc::new(Evil),
+        )
+        .unwrap();
+
+        Resolver {
+            roots: vec![root.ip()],
+            port,
+            timeout: Duration::from_millis(200),
+            ..Resolver::default()
+        }
+    }
+
+    fn addresses(response: &Packet) -> Vec<Ipv4Addr> {
+        response
+            .answers
+            .iter()
+            .filter_map(|record| match record {
+                Record::A { addr, .. } => Some(*addr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_follows_referrals() {
+        let response = resolver().resolve("WWW.example.com.", QType::A).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 1)]);
+        assert_eq!(response.questions[0].name, "www.example.com");
+    }
+
+    #[test]
+    fn test_resolves_missing_glue() {
+        let response = resolver().resolve("www.example.org", QType::A).unwrap();
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 2)]);
+    }
+
+    #[test]
+    fn test_chases_cnames() {
+        let resolver = resolver();
+
+        // Within the zone, which the server resolves for us
+        let response = resolver.resolve("alias.example.com", QType::A).unwrap();
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.answers[0].qtype(), QType::CNAME);
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 1)]);
+
+        // Out of the zone, into one that needs its own referrals
+        let response = resolver.resolve("elsewhere.example.com", QType::A).unwrap();
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 2)]);
+
+        match resolver.resolve("loop1.example.com", QType::A) {
+            Err(ResolveError::CnameChainTooLong { limit: 8 }) => {}
+            other => panic!("Expected the loop to be caught, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ignores_records_outside_the_zone() {
+        let resolver = resolver();
+
+        // The CNAME is evil.com's to give, the address of its target isn't.
+        let response = resolver.resolve("www.evil.com", QType::A).unwrap();
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 2)]);
+
+        let resolver = Resolver {
+            max_queries: 0,
+            ..resolver
+        };
+        let response = resolver.resolve("www.example.org", QType::A).unwrap();
+        assert_eq!(addresses(&response), vec![Ipv4Addr::new(192, 0, 2, 2)]);
+    }
+
+    #[test]
+    fn test_negative_answers() {
+        let resolver = resolver();
+
+        let response = resolver.resolve("nope.example.com", QType::A).unwrap();
+        assert_eq!(response.header.rcode, RCode::NameError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities[0].qtype(), QType::SOA);
+        assert_eq!(response.authorities[0].domain(), "example.com");
+
+        let response = resolver.resolve("www.example.com", QType::AAAA).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities[0].qtype(), QType::SOA);
+
+        // The NS records of the zone itself don't make it a referral.
+        let response = resolver.resolve("www.evil.com", QType::TXT).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities[0].qtype(), QType::NS);
+    }
+
+    #[test]
+    fn test_caches_answers() {
+        let resolver = resolver();
+        let first = resolver.resolve("alias.example.com", QType::A).unwrap();
+
+        // Nobody is asked the second time round: not even the root
+        let resolver = Resolver {
+            max_queries: 0,
+            ..resolver
+        };
+        let second = resolver.resolve("alias.example.com", QType::A).unwrap();
+        assert_eq!(addresses(&second), addresses(&first));
+        assert_eq!(second.answers.len(), 2);
+        assert!(second.answers[1].ttl() <= first.answers[1].ttl());
+
+        let cache = resolver.cache.as_ref().unwrap().lock().unwrap();
+        assert_eq!(cache.hits(), 2);
+    }
+
+    #[test]
+    fn test_caches_negative_answers() {
+        let resolver = resolver();
+        resolver.resolve("nope.example.com", QType::A).unwrap();
+        resolver.resolve("www.example.com", QType::AAAA).unwrap();
+
+        let resolver = Resolver {
+            max_queries: 0,
+            ..resolver
+        };
+        // NXDOMAIN holds for every type
+        let response = resolver.resolve("nope.example.com", QType::MX).unwrap();
+        assert_eq!(response.header.rcode, RCode::NameError);
+        assert_eq!(response.authorities[0].qtype(), QType::SOA);
+        assert!(response.authorities[0].ttl() <= 60);
+
+        let response = resolver.resolve("www.example.com", QType::AAAA).unwrap();
+        assert_eq!(response.header.rcode, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities[0].qtype(), QType::SOA);
+
+        // NODATA only holds for the one type
+        match resolver.resolve("www.example.com", QType::TXT) {
+            Err(ResolveError::TooManyQueries { limit: 0 }) => {}
+            other => panic!("Expected to need a query, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bounds_work() {
+        let resolver = Resolver {
+            max_queries: 2,
+            ..resolver()
+        };
+        match resolver.resolve("www.example.com", QType::A) {
+            Err(ResolveError::TooManyQueries { limit: 2 }) => {}
+            other => panic!("Expected to run out of queries, got {:?}", other),
+        }
+
+        match resolver.resolve("www.lame", QType::A) {
+            Err(ResolveError::NoUsableServers { zone }) => assert_eq!(zone, "lame"),
+            other => panic!("Expected no usable servers, got {:?}", other),
+        }
+    }
+}
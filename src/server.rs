@@ -0,0 +1,425 @@
+use crate::byte_packet_buffer::{BytePacketBuffer, DEFAULT_SIZE, MAX_SIZE};
+use crate::edns::Opt;
+use crate::error::WriteError;
+use crate::header::{Header, RCode};
+use crate::packet::Packet;
+use crate::tcp::{read_framed, write_framed};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The largest UDP response we are willing to send, whatever the client
+/// advertises in its OPT record.
+pub const MAX_UDP_PAYLOAD_SIZE: usize = 4096;
+
+/// How many UDP queries are answered at once. A handler that waits on
+/// other servers, like the `Forwarder`, then only holds up its own worker
+/// while it waits.
+pub const UDP_WORKERS: usize = 16;
+
+/// How long a TCP connection may sit idle before we close it. RFC 7766
+/// §6.2.3 leaves the value to the server but asks that idle connections be
+/// closed, so that they don't tie up resources meant for active clients.
+pub const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most TCP connections served at once. Connections beyond that are
+/// closed as soon as they are accepted.
+pub const MAX_TCP_CONNECTIONS: usize = 128;
+
+/// Turns a query into a response. The transport takes care of everything
+/// to do with the wire: parsing, truncation, and answering queries that
+/// can't be parsed.
+pub trait RequestHandler: Send + Sync {
+    fn handle(&self, request: &Packet) -> Packet;
+}
+
+/// An empty response to `request`: the same id, opcode and questions, and
+/// the RD and CD bits copied over as RFC 1035 and RFC 4035 require.
+pub fn response_to(request: &Packet) -> Packet {
+    Packet {
+        header: Header {
+            id: request.header.id,
+            is_reply: true,
+            opcode: request.header.opcode,
+            recursion_desired: request.header.recursion_desired,
+            checking_disabled: request.header.checking_disabled,
+            ..Header::default()
+        },
+        questions: request.questions.clone(),
+        ..Packet::default()
+    }
+}
+
+/// Serve `handler` over both UDP and TCP on `addr`, each in a background
+/// thread. Returns the address actually bound, which is useful when `addr`
+/// asks for port 0: the TCP listener is bound to the port picked for UDP.
+pub fn spawn<H: RequestHandler + 'static>(
+    addr: SocketAddr,
+    handler: Arc<H>,
+) -> io::Result<SocketAddr> {
+    let socket = UdpSocket::bind(addr)?;
+    let addr = socket.local_addr()?;
+    let listener = TcpListener::bind(addr)?;
+
+    let udp_handler = handler.clone();
+    thread::spawn(move || serve_udp(socket, udp_handler));
+    thread::spawn(move || serve_tcp(listener, handler));
+
+    Ok(addr)
+}
+
+/// Answer queries arriving on `socket` forever, in `UDP_WORKERS` threads
+/// sharing the socket, this one included.
+pub fn serve_udp<H: RequestHandler + 'static>(
+    socket: UdpSocket,
+    handler: Arc<H>,
+) -> io::Result<()> {
+    for _ in 1..UDP_WORKERS {
+        let socket = socket.try_clone()?;
+        let handler = handler.clone();
+        thread::spawn(move || answer_udp(&socket, &*handler));
+    }
+    answer_udp(&socket, &*handler);
+    Ok(())
+}
+
+/// Answer queries arriving on `socket`, one at a time, forever.
+fn answer_udp<H: RequestHandler>(socket: &UdpSocket, handler: &H) {
+    let mut req_buffer = BytePacketBuffer::with_capacity(MAX_UDP_PAYLOAD_SIZE);
+    loop {
+        let (len, src) = match socket.recv_from(&mut req_buffer.buf) {
+            Ok(received) => received,
+            Err(err) => {
+                eprintln!("udp receive failed: {}", err);
+                continue;
+            }
+        };
+
+        if let Some(response) = handle_bytes(handler, &req_buffer.buf[..len], false) {
+            if let Err(err) = socket.send_to(&response, src) {
+                eprintln!("udp send to {} failed: {}", src, err);
+            }
+        }
+    }
+}
+
+/// Accept connections on `listener` forever, serving each in its own
+/// thread, up to `MAX_TCP_CONNECTIONS` at a time.
+pub fn serve_tcp<H: RequestHandler + 'static>(
+    listener: TcpListener,
+    handler: Arc<H>,
+) -> io::Result<()> {
+    serve_tcp_with(listener, handler, TCP_IDLE_TIMEOUT, MAX_TCP_CONNECTIONS)
+}
+
+fn serve_tcp_with<H: RequestHandler + 'static>(
+    listener: TcpListener,
+    handler: Arc<H>,
+    idle_timeout: Duration,
+    max_connections: usize,
+) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                // Dropping the stream closes it.
+                if active.load(Ordering::SeqCst) >= max_connections {
+                    continue;
+                }
+                let slot = ConnectionSlot::take(&active);
+                let handler = handler.clone();
+                thread::spawn(move || serve_tcp_connection(stream, handler, idle_timeout, slot));
+            }
+            Err(err) => eprintln!("tcp accept failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// One of the connections counted against `MAX_TCP_CONNECTIONS`, given back
+/// when dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(active: &Arc<AtomicUsize>) -> ConnectionSlot {
+        active.fetch_add(1, Ordering::SeqCst);
+        ConnectionSlot(active.clone())
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Answer queries on a single connection, in order, until the client closes
+/// it or leaves it idle for `idle_timeout`. Clients may pipeline several
+/// queries.
+fn serve_tcp_connection<H: RequestHandler>(
+    mut stream: TcpStream,
+    handler: Arc<H>,
+    idle_timeout: Duration,
+    _slot: ConnectionSlot,
+) {
+    // A client too slow to read its responses counts as idle too.
+    if stream.set_read_timeout(Some(idle_timeout)).is_err()
+        || stream.set_write_timeout(Some(idle_timeout)).is_err()
+    {
+        return;
+    }
+
+    while let Ok(req_buffer) = read_framed(&mut stream) {
+        if let Some(response) = handle_bytes(&*handler, &req_buffer.buf, true) {
+            if write_framed(&mut stream, &response).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Turn the bytes of a query into the bytes of its response, or `None` if
+/// the message doesn't deserve one.
+pub fn handle_bytes<H: RequestHandler + ?Sized>(
+    handler: &H,
+    bytes: &[u8],
+    tcp: bool,
+) -> Option<Vec<u8>> {
+    let mut req_buffer = BytePacketBuffer::from_bytes(bytes);
+    let request = match Packet::from_buffer(&mut req_buffer) {
+        Ok(request) => request,
+        // A message we can't parse is always the client's fault.
+        Err(_) => return format_error(bytes),
+    };
+
+    // Never answer a response, so that two servers can't be made to bounce
+    // messages between each other.
+    if request.header.is_reply {
+        return None;
+    }
+
+    let mut response = handler.handle(&request);
+    if request.edns.is_some() && response.edns.is_none() {
+        response.edns = Some(Opt {
+            udp_payload_size: MAX_UDP_PAYLOAD_SIZE as u16,
+            ..Opt::default()
+        });
+    }
+
+    let max_size = if tcp {
+        MAX_SIZE
+    } else {
+        request.edns.as_ref().map_or(DEFAULT_SIZE, |opt| {
+            opt.effective_udp_payload_size().min(MAX_UDP_PAYLOAD_SIZE)
+        })
+    };
+
+    match write_response(&mut response, max_size) {
+        Ok(bytes) => Some(bytes),
+        Err(WriteError::BufferFull { .. }) => {
+            // Too large for UDP: send what fits with TC set, so that the
+            // client retries over TCP.
+            let mut truncated = Packet {
+                header: response.header.clone(),
+                questions: response.questions.clone(),
+                edns: response.edns.clone(),
+                ..Packet::default()
+            };
+            truncated.header.truncation = true;
+            write_response(&mut truncated, max_size).ok()
+        }
+        Err(err) => {
+            eprintln!("could not write response: {}", err);
+            let mut failure = response_to(&request);
+            // Failing to write a response is always our own fault.
+            failure.header.rcode = RCode::ServerFailure;
+            write_response(&mut failure, max_size).ok()
+        }
+    }
+}
+
+fn write_response(response: &mut Packet, max_size: usize) -> Result<Vec<u8>, WriteError> {
+    let mut res_buffer = BytePacketBuffer::with_max_size(max_size);
+    response.write(&mut res_buffer)?;
+    Ok(res_buffer.filled().to_vec())
+}
+
+/// A bare FORMERR response for a message we couldn't parse, as long as
+/// there is a header id to answer to.
+fn format_error(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.len() < 12 || bytes[2] & 0x80 != 0 {
+        return None;
+    }
+
+    let mut response = Packet {
+        header: Header {
+            id: u16::from_be_bytes([bytes[0], bytes[1]]),
+            is_reply: true,
+            opcode: ((bytes[2] >> 3) & 0x0F).into(),
+            rcode: RCode::FormatError,
+            ..Header::default()
+        },
+        ..Packet::default()
+    };
+    write_response(&mut response, DEFAULT_SIZE).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::question::{QClass, QType};
+    use crate::record::Record;
+    use std::io::Read;
+    use std::net::Ipv4Addr;
+    use std::time::Instant;
+
+    /// Answers every question with `count` A records
+    struct Repeat {
+        count: u8,
+    }
+
+    impl RequestHandler for Repeat {
+        fn handle(&self, request: &Packet) -> Packet {
+            let mut response = response_to(request);
+            for i in 0..self.count {
+                response.answers.push(Record::A {
+                    domain: request.questions[0].name.clone(),
+                    addr: Ipv4Addr::new(10, 0, 0, i),
+                    class: QClass::IN,
+                    ttl: 60,
+                });
+            }
+            response
+        }
+    }
+
+    fn client(server: SocketAddr) -> Client {
+        Client {
+            timeout: Duration::from_secs(1),
+            ..Client::new(server)
+        }
+    }
+
+    #[test]
+    fn test_serves_udp_and_tcp() {
+        let server = spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            Arc::new(Repeat { count: 2 }),
+        )
+        .unwrap();
+        let request = Client::build_query("codecrafters.io", QType::A);
+
+        let response = client(server).query_udp(&request).unwrap();
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.header.id, request.header.id);
+        assert!(response.header.recursion_desired);
+
+        let response = client(server).query_tcp(&request).unwrap();
+        assert_eq!(response.answers.len(), 2);
+    }
+
+    #[test]
+    fn test_truncates_large_udp_responses() {
+        let server = spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            Arc::new(Repeat { count: 100 }),
+        )
+        .unwrap();
+        let request = Client::build_query("codecrafters.io", QType::A);
+
+        let response = client(server).query_udp(&request).unwrap();
+        assert!(response.header.truncation);
+        assert!(response.answers.is_empty());
+
+        // The client retries over TCP by itself
+        let response = client(server).query(&request).unwrap();
+        assert!(!response.header.truncation);
+        assert_eq!(response.answers.len(), 100);
+    }
+
+    #[test]
+    fn test_honors_edns_payload_size() {
+        let server = spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            Arc::new(Repeat { count: 60 }),
+        )
+        .unwrap();
+        let mut request = Client::build_query("codecrafters.io", QType::A);
+        request.edns = Some(Opt::default());
+
+        let response = client(server).query_udp(&request).unwrap();
+        assert!(!response.header.truncation);
+        assert_eq!(response.answers.len(), 60);
+        assert!(response.edns.is_some());
+    }
+
+    #[test]
+    fn test_format_error() {
+        let handler = Repeat { count: 1 };
+
+        // A question whose name runs off the end of the message
+        let bytes = [0x12, 0x34, 0x01, 0, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'w'];
+        let response = handle_bytes(&handler, &bytes, false).unwrap();
+        let mut buffer = BytePacketBuffer::from_bytes(&response);
+        let response = Packet::from_buffer(&mut buffer).unwrap();
+        assert_eq!(response.header.id, 0x1234);
+        assert_eq!(response.header.rcode, RCode::FormatError);
+
+        // Too short to even have a header
+        assert_eq!(handle_bytes(&handler, &bytes[..4], false), None);
+    }
+
+    #[test]
+    fn test_ignores_responses() {
+        let mut request = Client::build_query("codecrafters.io", QType::A);
+        request.header.is_reply = true;
+        let mut buffer = BytePacketBuffer::new();
+        request.write(&mut buffer).unwrap();
+
+        assert_eq!(
+            handle_bytes(&Repeat { count: 1 }, buffer.filled(), false),
+            None
+        );
+    }
+
+    /// Whether the server closes `stream` within a second
+    fn closed_by_server(stream: &mut TcpStream) -> bool {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        matches!(stream.read(&mut [0; 1]), Ok(0))
+    }
+
+    #[test]
+    fn test_tcp_idle_timeout_and_connection_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap();
+        let handler = Arc::new(Repeat { count: 1 });
+        let idle_timeout = Duration::from_millis(500);
+        thread::spawn(move || serve_tcp_with(listener, handler, idle_timeout, 1));
+
+        let start = Instant::now();
+        let mut idle = TcpStream::connect(server).unwrap();
+        // Give the server time to take the first connection's slot.
+        thread::sleep(Duration::from_millis(50));
+
+        // No room for a second connection while the first is open, so it is
+        // closed straight away rather than after the idle timeout.
+        let mut extra = TcpStream::connect(server).unwrap();
+        assert!(closed_by_server(&mut extra));
+        assert!(start.elapsed() < idle_timeout);
+
+        assert!(closed_by_server(&mut idle));
+        assert!(start.elapsed() >= idle_timeout);
+
+        // Closing the idle connection gave its slot back.
+        thread::sleep(Duration::from_millis(50));
+        let request = Client::build_query("codecrafters.io", QType::A);
+        let response = client(server).query_tcp(&request).unwrap();
+        assert_eq!(response.answers.len(), 1);
+    }
+}
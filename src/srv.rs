@@ -0,0 +1,154 @@
+use crate::random::random_u64;
+use crate::record::Record;
+
+/// Order a set of SRV answers in the sequence a client should try them
+/// (RFC 2782): lowest priority first, and within a priority a weighted random
+/// selection so that each target is picked first in proportion to its
+/// weight. Records that aren't SRV records are left out.
+pub fn order_srv_records(records: &[Record]) -> Vec<Record> {
+    order_srv_records_with(records, |max| (random_u64() % (max as u64 + 1)) as u32)
+}
+
+/// `order_srv_records` with the source of randomness supplied by the
+/// caller. `random(max)` must return a number in `0..=max`.
+pub fn order_srv_records_with<F>(records: &[Record], mut random: F) -> Vec<Record>
+where
+    F: FnMut(u32) -> u32,
+{
+    let mut srvs: Vec<(u16, u16, &Record)> = records
+        .iter()
+        .filter_map(|record| match record {
+            Record::SRV {
+                priority, weight, ..
+            } => Some((*priority, *weight, record)),
+            _ => None,
+        })
+        .collect();
+
+    // Within a priority, records with weight 0 go to the front so that they
+    // have a very small chance of being selected first.
+    srvs.sort_by_key(|(priority, weight, _)| (*priority, *weight != 0));
+
+    let mut ordered = Vec::with_capacity(srvs.len());
+    let mut start = 0;
+    while start < srvs.len() {
+        let priority = srvs[start].0;
+        let end = srvs[start..]
+            .iter()
+            .position(|(p, _, _)| *p != priority)
+            .map_or(srvs.len(), |len| start + len);
+
+        let mut group: Vec<(u16, u16, &Record)> = srvs[start..end].to_vec();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|(_, weight, _)| *weight as u32).sum();
+            let pick = random(total);
+
+            // The first record whose running sum of weights reaches the
+            // random number is the next one selected.
+            let mut running = 0;
+            let index = group
+                .iter()
+                .position(|(_, weight, _)| {
+                    running += *weight as u32;
+                    running >= pick
+                })
+                .unwrap_or(group.len() - 1);
+
+            ordered.push(group.remove(index).2.clone());
+        }
+
+        start = end;
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::question::QClass;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> Record {
+        Record::SRV {
+            domain: "_grpc._tcp.codecrafters.io".to_string(),
+            priority,
+            weight,
+            port: 8443,
+            target: target.to_string(),
+            class: QClass::IN,
+            ttl: 300,
+        }
+    }
+
+    fn targets(records: &[Record]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| match record {
+                Record::SRV { target, .. } => target.as_str(),
+                _ => panic!("Wrong record type"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_orders_by_priority() {
+        let records = vec![srv(20, 0, "c"), srv(10, 0, "a"), srv(15, 0, "b")];
+        let ordered = order_srv_records(&records);
+        assert_eq!(targets(&ordered), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_weighted_selection_within_priority() {
+        let records = vec![
+            srv(10, 10, "light"),
+            srv(10, 90, "heavy"),
+            srv(20, 0, "backup"),
+        ];
+
+        // The running sums are light = 10, heavy = 100.
+        let ordered = order_srv_records_with(&records, |_| 5);
+        assert_eq!(targets(&ordered), ["light", "heavy", "backup"]);
+
+        let ordered = order_srv_records_with(&records, |max| max);
+        assert_eq!(targets(&ordered), ["heavy", "light", "backup"]);
+    }
+
+    #[test]
+    fn test_zero_weight_is_only_picked_by_zero() {
+        let records = vec![srv(10, 50, "weighted"), srv(10, 0, "unweighted")];
+
+        let ordered = order_srv_records_with(&records, |_| 0);
+        assert_eq!(targets(&ordered), ["unweighted", "weighted"]);
+
+        let ordered = order_srv_records_with(&records, |_| 1);
+        assert_eq!(targets(&ordered), ["weighted", "unweighted"]);
+    }
+
+    #[test]
+    fn test_weighted_selection_is_proportional() {
+        let records = vec![srv(10, 1, "light"), srv(10, 3, "heavy")];
+        // Draw every value the first selection can see once each. RFC 2782
+        // draws from 0..=total, so the heavy record wins 3 of the 5.
+        let heavy_first = (0..=4)
+            .filter(|&draw| {
+                let ordered = order_srv_records_with(&records, |max| draw.min(max));
+                targets(&ordered)[0] == "heavy"
+            })
+            .count();
+        assert_eq!(heavy_first, 3);
+    }
+
+    #[test]
+    fn test_ignores_other_records() {
+        let records = vec![
+            Record::A {
+                domain: "codecrafters.io".to_string(),
+                addr: "10.0.0.1".parse().unwrap(),
+                class: QClass::IN,
+                ttl: 60,
+            },
+            srv(10, 0, "a"),
+        ];
+        assert_eq!(targets(&order_srv_records(&records)), ["a"]);
+    }
+}
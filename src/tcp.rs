@@ -0,0 +1,223 @@
+// https://datatracker.ietf.org/doc/html/rfc1035#section-4.2.2
+// Messages sent over TCP connections use server port 53 (decimal).  The
+// message is prefixed with a two byte length field which gives the message
+// length, excluding the two byte length field.
+use crate::byte_packet_buffer::{BytePacketBuffer, MAX_SIZE};
+use crate::client::Client;
+use crate::error::ClientError;
+use crate::packet::Packet;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Read one length-prefixed message from a stream
+pub fn read_framed<R: Read>(reader: &mut R) -> io::Result<BytePacketBuffer> {
+    let mut len = [0; 2];
+    reader.read_exact(&mut len)?;
+    let len = u16::from_be_bytes(len) as usize;
+
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(BytePacketBuffer::from_bytes(&bytes))
+}
+
+/// Write one message to a stream, prefixed with its length
+pub fn write_framed<W: Write>(writer: &mut W, message: &[u8]) -> io::Result<()> {
+    // Length and message go out in a single write so that they end up in
+    // the same segment.
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    framed.extend_from_slice(message);
+    writer.write_all(&framed)?;
+    writer.flush()
+}
+
+/// A TCP connection to a nameserver, on which any number of queries can be
+/// sent before their responses are read (RFC 7766 §6.2.1.1).
+pub struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl TcpConnection {
+    /// Connect to `server`, giving up on the connection attempt and on every
+    /// later read after `timeout`
+    pub fn connect(server: SocketAddr, timeout: Duration) -> Result<TcpConnection, ClientError> {
+        let stream = TcpStream::connect_timeout(&server, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+
+        Ok(TcpConnection { stream })
+    }
+
+    pub fn send(&mut self, request: &Packet) -> Result<(), ClientError> {
+        let mut request = request.clone();
+        let mut buffer = BytePacketBuffer::with_max_size(MAX_SIZE);
+        request.write(&mut buffer)?;
+
+        write_framed(&mut self.stream, buffer.filled())?;
+
+        Ok(())
+    }
+
+    /// Read the next response from the connection, whichever query it
+    /// answers
+    pub fn receive(&mut self) -> Result<Packet, ClientError> {
+        let mut buffer = read_framed(&mut self.stream)?;
+
+        Ok(Packet::from_buffer(&mut buffer)?)
+    }
+
+    /// Send `request` and read responses until the one that answers it
+    pub fn query(&mut self, request: &Packet) -> Result<Packet, ClientError> {
+        self.send(request)?;
+
+        loop {
+            let response = self.receive()?;
+            if Client::matches(request, &response) {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// Pipeline `requests` on this connection: send them all, then collect
+    /// the responses, which a server may send in any order. The responses
+    /// are returned in the order of their requests, so every request needs
+    /// a distinct id; nothing is sent if two of them share one.
+    pub fn query_many(&mut self, requests: &[Packet]) -> Result<Vec<Packet>, ClientError> {
+        let mut ids = HashSet::new();
+        if let Some(request) = requests
+            .iter()
+            .find(|request| !ids.insert(request.header.id))
+        {
+            return Err(ClientError::DuplicateId {
+                id: request.header.id,
+            });
+        }
+
+        for request in requests {
+            self.send(request)?;
+        }
+
+        let mut responses: HashMap<u16, Packet> = HashMap::new();
+        while responses.len() < requests.len() {
+            let response = self.receive()?;
+            let matched = requests.iter().find(|request| {
+                !responses.contains_key(&request.header.id) && Client::matches(request, &response)
+            });
+            if let Some(request) = matched {
+                responses.insert(request.header.id, response);
+            }
+        }
+
+        Ok(requests
+            .iter()
+            .filter_map(|request| responses.remove(&request.header.id))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::question::QType;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_framing_round_trip() {
+        let mut stream = Vec::new();
+        write_framed(&mut stream, &[1, 2, 3]).unwrap();
+        write_framed(&mut stream, &[]).unwrap();
+        assert_eq!(stream, [0, 3, 1, 2, 3, 0, 0]);
+
+        let mut reader = &stream[..];
+        assert_eq!(read_framed(&mut reader).unwrap().buf, [1, 2, 3]);
+        assert!(read_framed(&mut reader).unwrap().buf.is_empty());
+        assert!(read_framed(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_truncated_frame() {
+        let stream = [0, 3, 1, 2];
+        let mut reader = &stream[..];
+        match read_framed(&mut reader) {
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            Ok(_) => panic!("Expected the frame to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_pipelined_queries() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                let mut buffer = read_framed(&mut stream).unwrap();
+                requests.push(Packet::from_buffer(&mut buffer).unwrap());
+            }
+            // Answer in reverse order
+            for mut response in requests.into_iter().rev() {
+                response.header.is_reply = true;
+                let mut buffer = BytePacketBuffer::new();
+                response.write(&mut buffer).unwrap();
+                write_framed(&mut stream, buffer.filled()).unwrap();
+            }
+        });
+
+        let mut requests = vec![
+            Client::build_query("a.codecrafters.io", QType::A),
+            Client::build_query("b.codecrafters.io", QType::AAAA),
+            Client::build_query("c.codecrafters.io", QType::MX),
+        ];
+        for (i, request) in requests.iter_mut().enumerate() {
+            request.header.id = i as u16;
+        }
+
+        let mut connection = TcpConnection::connect(addr, Duration::from_secs(2)).unwrap();
+        let responses = connection.query_many(&requests).unwrap();
+        assert_eq!(responses.len(), 3);
+        for (request, response) in requests.iter().zip(&responses) {
+            assert!(Client::matches(request, response));
+        }
+    }
+
+    #[test]
+    fn test_pipelined_duplicate_ids() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || listener.accept().unwrap());
+
+        let mut requests = vec![
+            Client::build_query("a.codecrafters.io", QType::A),
+            Client::build_query("b.codecrafters.io", QType::A),
+        ];
+        requests[1].header.id = requests[0].header.id;
+
+        let mut connection = TcpConnection::connect(addr, Duration::from_millis(100)).unwrap();
+        match connection.query_many(&requests) {
+            Err(ClientError::DuplicateId { id }) => assert_eq!(id, requests[0].header.id),
+            other => panic!("Expected a duplicate id error, got {:?}", other),
+        }
+        drop(handle);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || listener.accept().unwrap());
+
+        let mut connection = TcpConnection::connect(addr, Duration::from_millis(100)).unwrap();
+        let request = Client::build_query("codecrafters.io", QType::A);
+        match connection.query(&request) {
+            Err(ClientError::Timeout { .. }) => {}
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+        drop(handle);
+    }
+}
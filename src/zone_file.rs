@@ -0,0 +1,923 @@
+// https://datatracker.ietf.org/doc/html/rfc1035#section-5.1
+// The format of these files is a sequence of entries.  Entries are
+// predominantly line-oriented, though parentheses can be used to continue
+// a list of items across a line boundary, and text literals can contain
+// CRLF within the text.
+//
+//     $ORIGIN <domain-name> [<comment>]
+//     $INCLUDE <file-name> [<domain-name>] [<comment>]
+//     <domain-name><rr> [<comment>]
+//     <blank><rr> [<comment>]
+//
+// <rr> contents take one of the following forms:
+//
+//     [<TTL>] [<class>] <type> <RDATA>
+//     [<class>] [<TTL>] <type> <RDATA>
+use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::ZoneFileError;
+use crate::name::canonical_cmp;
+use crate::question::{QClass, QType};
+use crate::record::{parse_generic_rdata, Record};
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+/// How deeply `$INCLUDE`s may nest, which stops a file including itself
+/// from recursing forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Parse the records of a zone file read from `text`. Relative names are
+/// completed with `origin` until a `$ORIGIN` changes it. `$INCLUDE`d paths
+/// are relative to the working directory.
+pub fn parse(text: &str, origin: &str) -> Result<Vec<Record>, ZoneFileError> {
+    let mut parser = Parser::new(None, origin, 0);
+    parser.parse(text)?;
+    Ok(parser.records)
+}
+
+/// Parse the zone file at `path`. `$INCLUDE`d paths are relative to the
+/// directory of the file that includes them.
+pub fn parse_file(path: &Path, origin: &str) -> Result<Vec<Record>, ZoneFileError> {
+    let mut parser = Parser::new(Some(path.to_path_buf()), origin, 0);
+    parser.parse_file()?;
+    Ok(parser.records)
+}
+
+/// Write `records` as a zone file which `parse` reads back as the same
+/// records. The output is canonical: one record per line with absolute
+/// names, the SOA first, then the rest sorted by owner name in the order of
+/// RFC 4034 §6.1, by type and by RDATA, without duplicates.
+pub fn write(records: &[Record]) -> String {
+    let mut lines: Vec<(&Record, String)> = records
+        .iter()
+        .map(|record| (record, record.to_string()))
+        .collect();
+    lines.sort_by(|(a, a_line), (b, b_line)| {
+        let is_soa = |record: &Record| record.qtype() == QType::SOA;
+        is_soa(b)
+            .cmp(&is_soa(a))
+            .then_with(|| canonical_cmp(a.domain(), b.domain()))
+            .then_with(|| a.qtype().to_num().cmp(&b.qtype().to_num()))
+            .then_with(|| a_line.cmp(b_line))
+    });
+    lines.dedup_by(|(_, a), (_, b)| a == b);
+
+    let mut out = String::new();
+    for (_, line) in lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// A token of an entry, with escapes still in place so that `\.` can be
+/// told apart from `.` in names and `\#` from `#` in RDATA.
+#[derive(Debug)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+/// An entry: a line, or several when continued with parentheses.
+#[derive(Debug)]
+struct Entry {
+    line: usize,
+    /// Whether the entry starts with a blank, which means it has the same
+    /// owner as the previous record
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+struct Parser {
+    file: Option<PathBuf>,
+    origin: String,
+    depth: usize,
+    default_ttl: Option<u32>,
+    last_owner: Option<String>,
+    last_ttl: Option<u32>,
+    last_class: QClass,
+    records: Vec<Record>,
+    line: usize,
+}
+
+impl Parser {
+    fn new(file: Option<PathBuf>, origin: &str, depth: usize) -> Parser {
+        Parser {
+            file,
+            origin: origin.trim_end_matches('.').to_lowercase(),
+            depth,
+            default_ttl: None,
+            last_owner: None,
+            last_ttl: None,
+            last_class: QClass::IN,
+            records: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ZoneFileError {
+        ZoneFileError {
+            file: self.file.clone(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn parse_file(&mut self) -> Result<(), ZoneFileError> {
+        let path = self.file.clone().unwrap_or_default();
+        let text = fs::read_to_string(&path).map_err(|err| self.error(err.to_string()))?;
+        self.parse(&text)
+    }
+
+    fn parse(&mut self, text: &str) -> Result<(), ZoneFileError> {
+        for entry in self.tokenize(text)? {
+            self.line = entry.line;
+            self.entry(entry)?;
+        }
+        Ok(())
+    }
+
+    /// Split `text` into entries, dropping comments and joining lines
+    /// continued with parentheses.
+    fn tokenize(&mut self, text: &str) -> Result<Vec<Entry>, ZoneFileError> {
+        let mut entries = Vec::new();
+        let mut current: Option<Entry> = None;
+        let mut depth = 0;
+
+        for (i, line) in text.lines().enumerate() {
+            self.line = i + 1;
+            let entry = current.get_or_insert_with(|| Entry {
+                line: i + 1,
+                blank_owner: line.starts_with([' ', '\t']),
+                tokens: Vec::new(),
+            });
+
+            let mut chars = line.chars();
+            let mut token: Option<Token> = None;
+            while let Some(c) = chars.next() {
+                match c {
+                    ' ' | '\t' | '\r' => entry.tokens.extend(token.take()),
+                    ';' => break,
+                    '(' => {
+                        entry.tokens.extend(token.take());
+                        depth += 1;
+                    }
+                    ')' => {
+                        entry.tokens.extend(token.take());
+                        if depth == 0 {
+                            return Err(self.error("unbalanced ')'"));
+                        }
+                        depth -= 1;
+                    }
+                    '"' => {
+                        entry.tokens.extend(token.take());
+                        let mut text = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some('\\') => {
+                                    text.push('\\');
+                                    text.extend(chars.next());
+                                }
+                                Some(c) => text.push(c),
+                                None => return Err(self.error("unterminated quoted string")),
+                            }
+                        }
+                        entry.tokens.push(Token { text, quoted: true });
+                    }
+                    _ => {
+                        let token = token.get_or_insert_with(|| Token {
+                            text: String::new(),
+                            quoted: false,
+                        });
+                        token.text.push(c);
+                        // An escaped character never has a special meaning.
+                        if c == '\\' {
+                            token.text.extend(chars.next());
+                        }
+                    }
+                }
+            }
+            entry.tokens.extend(token.take());
+
+            if depth == 0 {
+                entries.extend(current.take().filter(|entry| !entry.tokens.is_empty()));
+            }
+        }
+
+        if depth > 0 {
+            if let Some(entry) = current {
+                self.line = entry.line;
+            }
+            return Err(self.error("unbalanced '('"));
+        }
+        Ok(entries)
+    }
+
+    fn entry(&mut self, entry: Entry) -> Result<(), ZoneFileError> {
+        let mut tokens = entry.tokens.iter();
+
+        let first = &entry.tokens[0];
+        if !entry.blank_owner && !first.quoted && first.text.starts_with('$') {
+            return self.directive(&first.text, &entry.tokens[1..]);
+        }
+
+        let owner = if entry.blank_owner {
+            self.last_owner
+                .clone()
+                .ok_or_else(|| self.error("no previous owner name to repeat"))?
+        } else {
+            self.name(tokens.next().unwrap())?
+        };
+
+        // The TTL and class may come in either order, and both may be left
+        // out.
+        let mut ttl = None;
+        let mut class = None;
+        let qtype = loop {
+            let token = tokens
+                .next()
+                .ok_or_else(|| self.error("missing record type"))?;
+            if let (None, Some(parsed)) = (class, QClass::from_mnemonic(&token.text)) {
+                class = Some(parsed);
+            } else if let (None, Some(parsed)) = (ttl, parse_ttl(&token.text)) {
+                ttl = Some(parsed);
+            } else {
+                break QType::from_mnemonic(&token.text)
+                    .ok_or_else(|| self.error(format!("unknown type {:?}", token.text)))?;
+            }
+        };
+        let class = class.unwrap_or(self.last_class);
+
+        let rdata: Vec<&Token> = tokens.collect();
+        let record = self.record(owner.clone(), qtype, class, ttl.unwrap_or(0), &rdata)?;
+
+        // RFC 2308 §4 makes $TTL the default; without one, RFC 1035 falls
+        // back on the last TTL given explicitly, and the SOA minimum is what
+        // older files relied on for the SOA itself.
+        let explicit_ttl = ttl;
+        let ttl = match (ttl, self.default_ttl, self.last_ttl, &record) {
+            (Some(ttl), ..) => ttl,
+            (None, Some(ttl), ..) | (None, None, Some(ttl), _) => ttl,
+            (None, None, None, Record::SOA { minimum, .. }) => *minimum,
+            _ => return Err(self.error("no TTL given and no $TTL to default to")),
+        };
+        let mut record = record;
+        record.set_ttl(ttl);
+
+        self.last_owner = Some(owner);
+        self.last_class = class;
+        if explicit_ttl.is_some() {
+            self.last_ttl = explicit_ttl;
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, args: &[Token]) -> Result<(), ZoneFileError> {
+        match name.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let [origin] = args else {
+                    return Err(self.error("$ORIGIN takes one domain name"));
+                };
+                self.origin = self.name(origin)?;
+            }
+            "$TTL" => {
+                let ttl = match args {
+                    [ttl] => parse_ttl(&ttl.text),
+                    _ => None,
+                };
+                self.default_ttl = Some(ttl.ok_or_else(|| self.error("$TTL takes one TTL"))?);
+            }
+            "$INCLUDE" => {
+                let (path, origin) = match args {
+                    [path] => (path, self.origin.clone()),
+                    [path, origin] => (path, self.name(origin)?),
+                    _ => {
+                        return Err(self.error("$INCLUDE takes a file name and an optional origin"))
+                    }
+                };
+                if self.depth >= MAX_INCLUDE_DEPTH {
+                    return Err(self.error("$INCLUDE nested too deeply"));
+                }
+
+                let path = PathBuf::from(self.unescape_text(path)?);
+                let path = match self.file.as_ref().and_then(|file| file.parent()) {
+                    Some(dir) if path.is_relative() => dir.join(path),
+                    _ => path,
+                };
+
+                // The included file gets its own origin, but whatever it
+                // sets doesn't carry back into this one.
+                let mut included = Parser::new(Some(path), &origin, self.depth + 1);
+                included.default_ttl = self.default_ttl;
+                included.last_ttl = self.last_ttl;
+                included.last_class = self.last_class;
+                included.parse_file()?;
+                self.records.append(&mut included.records);
+            }
+            _ => return Err(self.error(format!("unknown directive {}", name))),
+        }
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        domain: String,
+        qtype: QType,
+        class: QClass,
+        ttl: u32,
+        rdata: &[&Token],
+    ) -> Result<Record, ZoneFileError> {
+        if rdata
+            .first()
+            .is_some_and(|token| token.text == "\\#" && !token.quoted)
+        {
+            return self.generic_record(domain, qtype, class, ttl, rdata);
+        }
+
+        let record = match (qtype, rdata) {
+            (QType::A, [addr]) => Record::A {
+                domain,
+                addr: self.field::<Ipv4Addr>(addr, "IPv4 address")?,
+                class,
+                ttl,
+            },
+            (QType::AAAA, [addr]) => Record::AAAA {
+                domain,
+                addr: self.field::<Ipv6Addr>(addr, "IPv6 address")?,
+                class,
+                ttl,
+            },
+            (QType::NS, [host]) => Record::NS {
+                domain,
+                host: self.name(host)?,
+                class,
+                ttl,
+            },
+            (QType::CNAME, [host]) => Record::CNAME {
+                domain,
+                host: self.name(host)?,
+                class,
+                ttl,
+            },
+            (QType::PTR, [host]) => Record::PTR {
+                domain,
+                host: self.name(host)?,
+                class,
+                ttl,
+            },
+            (QType::MX, [priority, host]) => Record::MX {
+                domain,
+                priority: self.field(priority, "priority")?,
+                host: self.name(host)?,
+                class,
+                ttl,
+            },
+            (QType::SOA, [mname, rname, serial, refresh, retry, expire, minimum]) => Record::SOA {
+                domain,
+                mname: self.name(mname)?,
+                rname: self.name(rname)?,
+                serial: self.field(serial, "serial")?,
+                refresh: self.ttl(refresh)?,
+                retry: self.ttl(retry)?,
+                expire: self.ttl(expire)?,
+                minimum: self.ttl(minimum)?,
+                class,
+                ttl,
+            },
+            (QType::TXT, strings) if !strings.is_empty() => {
+                let data = strings
+                    .iter()
+                    .map(|token| {
+                        let string = self.unescape(token)?;
+                        if string.len() > 255 {
+                            return Err(self.error("character-string exceeds 255 bytes"));
+                        }
+                        Ok(string)
+                    })
+                    .collect::<Result<_, _>>()?;
+                Record::TXT {
+                    domain,
+                    data,
+                    class,
+                    ttl,
+                }
+            }
+            (QType::SRV, [priority, weight, port, target]) => Record::SRV {
+                domain,
+                priority: self.field(priority, "priority")?,
+                weight: self.field(weight, "weight")?,
+                port: self.field(port, "port")?,
+                target: self.name(target)?,
+                class,
+                ttl,
+            },
+            (
+                QType::A
+                | QType::AAAA
+                | QType::NS
+                | QType::CNAME
+                | QType::PTR
+                | QType::MX
+                | QType::SOA
+                | QType::TXT
+                | QType::SRV,
+                _,
+            ) => {
+                return Err(self.error(format!(
+                    "wrong number of RDATA fields for {:?}: {}",
+                    qtype,
+                    rdata.len()
+                )))
+            }
+            _ => {
+                return Err(self.error(format!(
+                    "type {:?} can only be given in the \\# generic format",
+                    qtype
+                )))
+            }
+        };
+        Ok(record)
+    }
+
+    /// A record whose RDATA is in the generic format of RFC 3597 §5, which
+    /// is allowed for known types too: those are decoded as if they had come
+    /// off the wire.
+    fn generic_record(
+        &self,
+        domain: String,
+        qtype: QType,
+        class: QClass,
+        ttl: u32,
+        rdata: &[&Token],
+    ) -> Result<Record, ZoneFileError> {
+        if qtype == QType::OPT {
+            return Err(self.error("OPT is a pseudo-record and can't be in a zone"));
+        }
+
+        let text: Vec<&str> = rdata.iter().map(|token| token.text.as_str()).collect();
+        let data = parse_generic_rdata(&text.join(" "))
+            .ok_or_else(|| self.error("invalid \\# RDATA: the length must match the hex data"))?;
+        if data.len() > u16::MAX as usize {
+            return Err(self.error("RDATA exceeds 65535 bytes"));
+        }
+
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&qtype.to_num().to_be_bytes());
+        bytes.extend_from_slice(&class.to_num().to_be_bytes());
+        bytes.extend_from_slice(&ttl.to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&data);
+
+        let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+        let mut record = Record::read(&mut buffer)
+            .map_err(|err| self.error(format!("invalid \\# RDATA: {}", err)))?;
+        if buffer.pos() != bytes.len() {
+            return Err(self.error(format!("invalid \\# RDATA for {:?}", qtype)));
+        }
+
+        // Put back the owner name, which the root stood in for.
+        match &mut record {
+            Record::Unknown { domain: owner, .. }
+            | Record::A { domain: owner, .. }
+            | Record::AAAA { domain: owner, .. }
+            | Record::NS { domain: owner, .. }
+            | Record::CNAME { domain: owner, .. }
+            | Record::PTR { domain: owner, .. }
+            | Record::MX { domain: owner, .. }
+            | Record::SOA { domain: owner, .. }
+            | Record::TXT { domain: owner, .. }
+            | Record::SRV { domain: owner, .. } => *owner = domain,
+        }
+        Ok(record)
+    }
+
+    /// A domain name in the form `read_qname` produces, completed with the
+    /// origin unless it is absolute
+    fn name(&self, token: &Token) -> Result<String, ZoneFileError> {
+        if token.text == "@" && !token.quoted {
+            return Ok(self.origin.clone());
+        }
+
+        let (text, absolute) = match token.text.strip_suffix('.') {
+            // A trailing `\.` is an escaped dot inside the last label.
+            Some(text) if !text.ends_with('\\') || text.ends_with("\\\\") => (text, true),
+            _ => (token.text.as_str(), false),
+        };
+        if text.is_empty() {
+            return if absolute {
+                Ok(String::new())
+            } else {
+                Err(self.error("empty domain name"))
+            };
+        }
+
+        let mut labels = Vec::new();
+        for label in split_labels(text) {
+            let label = self.unescape_text(&Token {
+                text: label.to_string(),
+                quoted: false,
+            })?;
+            if label.is_empty() {
+                return Err(self.error(format!("empty label in {:?}", token.text)));
+            }
+            if label.contains('.') {
+                return Err(self.error(format!(
+                    "dots within labels are not supported: {:?}",
+                    token.text
+                )));
+            }
+            if label.len() > 63 {
+                return Err(self.error(format!("label {:?} exceeds 63 characters", label)));
+            }
+            labels.push(label.to_lowercase());
+        }
+
+        let mut name = labels.join(".");
+        if !absolute && !self.origin.is_empty() {
+            name.push('.');
+            name.push_str(&self.origin);
+        }
+        // Each label costs its length plus a length byte, and the root one.
+        if name.len() + 2 > 255 {
+            return Err(self.error(format!("name {:?} exceeds 255 bytes", name)));
+        }
+        Ok(name)
+    }
+
+    fn ttl(&self, token: &Token) -> Result<u32, ZoneFileError> {
+        parse_ttl(&token.text).ok_or_else(|| self.error(format!("invalid TTL {:?}", token.text)))
+    }
+
+    fn field<T: std::str::FromStr>(&self, token: &Token, what: &str) -> Result<T, ZoneFileError> {
+        token
+            .text
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} {:?}", what, token.text)))
+    }
+
+    /// The bytes a token stands for, with `\X` and `\DDD` escapes resolved
+    fn unescape(&self, token: &Token) -> Result<Vec<u8>, ZoneFileError> {
+        let mut bytes = Vec::with_capacity(token.text.len());
+        let mut chars = token.text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+
+            match chars.next() {
+                Some(d) if d.is_ascii_digit() => {
+                    let digits: String = [Some(d), chars.next(), chars.next()]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    let byte = match digits.parse::<u16>() {
+                        Ok(byte) if digits.len() == 3 && byte <= 255 => byte as u8,
+                        _ => return Err(self.error(format!("invalid escape \\{}", digits))),
+                    };
+                    bytes.push(byte);
+                }
+                Some(c) => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                None => return Err(self.error("escape at the end of a token")),
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn unescape_text(&self, token: &Token) -> Result<String, ZoneFileError> {
+        String::from_utf8(self.unescape(token)?)
+            .map_err(|_| self.error(format!("{:?} is not valid UTF-8", token.text)))
+    }
+}
+
+/// Split a name on the dots that aren't escaped
+fn split_labels(text: &str) -> Vec<&str> {
+    let mut labels = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '.' => {
+                labels.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    labels.push(&text[start..]);
+    labels
+}
+
+/// A TTL in seconds, or in the units BIND accepts, e.g. `1h30m` or `2W`
+pub fn parse_ttl(text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(seconds) = text.parse() {
+        return Some(seconds);
+    }
+
+    let mut total: u32 = 0;
+    let mut value: Option<u32> = None;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = Some(value.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(value.take()?.checked_mul(unit)?)?;
+    }
+    if value.is_some() {
+        return None;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::random_u64;
+    use std::fs;
+
+    const ZONE: &str = r#"
+$ORIGIN example.com.
+$TTL 1h
+@   IN  SOA ns1 hostmaster (
+            2024010101 ; serial
+            2h         ; refresh
+            3600       ; retry
+            2w         ; expire
+            300 )      ; minimum
+    IN  NS  ns1
+    IN  MX  10 mail.example.com.
+ns1     A   192.0.2.53
+www 60  IN  A   192.0.2.1
+        IN  60  AAAA 2001:db8::1
+txt     TXT "v=spf1 -all" "a \"quoted\" \059 semicolon" plain
+_sip._tcp SRV 10 60 5060 sip
+alias   CNAME www
+"#;
+
+    fn a(domain: &str, addr: [u8; 4], ttl: u32) -> Record {
+        Record::A {
+            domain: domain.to_string(),
+            addr: Ipv4Addr::from(addr),
+            class: QClass::IN,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn test_parse_zone() {
+        let records = parse(ZONE, "").unwrap();
+        assert_eq!(records.len(), 9);
+
+        assert_eq!(
+            records[0],
+            Record::SOA {
+                domain: "example.com".to_string(),
+                mname: "ns1.example.com".to_string(),
+                rname: "hostmaster.example.com".to_string(),
+                serial: 2024010101,
+                refresh: 7200,
+                retry: 3600,
+                expire: 1209600,
+                minimum: 300,
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+        assert_eq!(
+            records[1],
+            Record::NS {
+                domain: "example.com".to_string(),
+                host: "ns1.example.com".to_string(),
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+        assert_eq!(
+            records[2],
+            Record::MX {
+                domain: "example.com".to_string(),
+                priority: 10,
+                host: "mail.example.com".to_string(),
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+        assert_eq!(records[3], a("ns1.example.com", [192, 0, 2, 53], 3600));
+        assert_eq!(records[4], a("www.example.com", [192, 0, 2, 1], 60));
+        assert_eq!(
+            records[5],
+            Record::AAAA {
+                domain: "www.example.com".to_string(),
+                addr: "2001:db8::1".parse().unwrap(),
+                class: QClass::IN,
+                ttl: 60,
+            }
+        );
+        assert_eq!(
+            records[6],
+            Record::TXT {
+                domain: "txt.example.com".to_string(),
+                data: vec![
+                    b"v=spf1 -all".to_vec(),
+                    b"a \"quoted\" ; semicolon".to_vec(),
+                    b"plain".to_vec(),
+                ],
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+        assert_eq!(
+            records[7],
+            Record::SRV {
+                domain: "_sip._tcp.example.com".to_string(),
+                priority: 10,
+                weight: 60,
+                port: 5060,
+                target: "sip.example.com".to_string(),
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+        assert_eq!(
+            records[8],
+            Record::CNAME {
+                domain: "alias.example.com".to_string(),
+                host: "www.example.com".to_string(),
+                class: QClass::IN,
+                ttl: 3600,
+            }
+        );
+    }
+
+    #[test]
+    fn test_origin_argument_and_ttl_fallback() {
+        // Without $TTL the last explicit TTL carries over (RFC 1035 §5.1)
+        let records = parse("www 120 A 192.0.2.1\nWWW2 A 192.0.2.2\n", "Example.COM.").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                a("www.example.com", [192, 0, 2, 1], 120),
+                a("www2.example.com", [192, 0, 2, 2], 120),
+            ]
+        );
+
+        // The SOA minimum stands in for a missing TTL on the SOA itself
+        let records = parse("@ SOA ns hostmaster 1 2 3 4 5\n", "example.com").unwrap();
+        assert_eq!(records[0].ttl(), 5);
+    }
+
+    #[test]
+    fn test_escaped_names() {
+        let records = parse("a\\066c 60 A 192.0.2.1\n", "example.com").unwrap();
+        assert_eq!(records[0].domain(), "abc.example.com");
+
+        let err = parse("a\\.b 60 A 192.0.2.1\n", "example.com").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn test_generic_rdata() {
+        let records = parse(
+            "$TTL 60\n\
+             a TYPE1 \\# 4 c0000201\n\
+             b CLASS1 TYPE65534 \\# 3 ( 01 02\n 03 )\n",
+            "example.com",
+        )
+        .unwrap();
+        assert_eq!(records[0], a("a.example.com", [192, 0, 2, 1], 60));
+        assert_eq!(
+            records[1],
+            Record::Unknown {
+                domain: "b.example.com".to_string(),
+                qtype: 65534,
+                class: QClass::IN,
+                data: vec![1, 2, 3],
+                ttl: 60,
+            }
+        );
+
+        // A generic A record that isn't 4 bytes long
+        assert!(parse("a 60 A \\# 3 c00002\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn test_line_numbered_errors() {
+        let cases = [
+            ("$TTL 60\nwww A 192.0.2.1\nwww A 192.0.2.256\n", 3),
+            ("$TTL 60\n\nwww MX ( 10\n)\n", 3),
+            ("$TTL 60\nwww BOGUS 1\n", 2),
+            ("www A 192.0.2.1\n", 1),
+            ("$TTL 60\n    A 192.0.2.1\n", 2),
+            ("$TTL 60\nwww TXT \"open\n", 2),
+            ("$TTL 60\nwww SOA ( ns hostmaster\n 1 2 3 4 5\n", 2),
+            ("$TTL 60\nwww A 192.0.2.1 )\n", 2),
+            ("$BOGUS\n", 1),
+        ];
+        for (text, line) in cases {
+            let err = parse(text, "example.com").unwrap_err();
+            assert_eq!(err.line, line, "{:?}: {}", text, err);
+            assert_eq!(err.file, None);
+        }
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("zone-file-{:x}", random_u64()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("main.zone"),
+            "$ORIGIN example.com.\n$TTL 60\nwww A 192.0.2.1\n\
+             $INCLUDE hosts.zone sub.example.com.\nmail A 192.0.2.25\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hosts.zone"),
+            "$ORIGIN inner.example.com.\nhost A 192.0.2.2\nbad\n",
+        )
+        .unwrap();
+
+        let err = parse_file(&dir.join("main.zone"), "").unwrap_err();
+        assert_eq!(err.file, Some(dir.join("hosts.zone")));
+        assert_eq!(err.line, 3);
+
+        fs::write(dir.join("hosts.zone"), "host A 192.0.2.2\n").unwrap();
+        let records = parse_file(&dir.join("main.zone"), "").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                a("www.example.com", [192, 0, 2, 1], 60),
+                a("host.sub.example.com", [192, 0, 2, 2], 60),
+                // The origin set before the $INCLUDE still applies
+                a("mail.example.com", [192, 0, 2, 25], 60),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut records = parse(ZONE, "").unwrap();
+        records.push(Record::TXT {
+            domain: "odd\\ \"name\";.example.com".to_string(),
+            data: vec![b"tab\there \\ \"quote\" \xff".to_vec(), vec![]],
+            class: QClass::CH,
+            ttl: 0,
+        });
+        records.push(Record::Unknown {
+            domain: "example.com".to_string(),
+            qtype: 13,
+            class: QClass::Unknown(42),
+            data: vec![1, b'x', 1, b'y'],
+            ttl: 60,
+        });
+        records.push(a("www.example.com", [192, 0, 2, 1], 60));
+
+        let text = write(&records);
+        assert!(text.starts_with("example.com. 3600 IN SOA "));
+        assert!(text.contains("\nexample.com. 60 CLASS42 HINFO \\# 4 01780179\n"));
+
+        let parsed = parse(&text, "").unwrap();
+        // The duplicate www record is gone
+        assert_eq!(parsed.len(), records.len() - 1);
+        for record in &records {
+            assert!(parsed.contains(record), "{} was lost", record);
+        }
+        // Writing is canonical: doing it again changes nothing
+        assert_eq!(write(&parsed), text);
+
+        let mut reversed = records.clone();
+        reversed.reverse();
+        assert_eq!(write(&reversed), text);
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("2W"), Some(1209600));
+        assert_eq!(parse_ttl("1d1"), None);
+        assert_eq!(parse_ttl("h"), None);
+        assert_eq!(parse_ttl(""), None);
+        assert_eq!(parse_ttl("99999999999"), None);
+    }
+}